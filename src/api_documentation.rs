@@ -4,9 +4,12 @@ use tracing::info;
 
 use aide::{
     axum::{ApiRouter, IntoApiResponse},
-    openapi::{Info, OpenApi},
+    openapi::{Info, OpenApi, SecurityScheme},
+    transform::{TransformOpenApi, TransformOperation},
 };
-use axum::response::IntoResponse;
+use axum::{Json, http::StatusCode, response::IntoResponse};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use std::{convert::Infallible, sync::OnceLock};
 
@@ -25,6 +28,65 @@ pub async fn serve_api() -> impl IntoApiResponse {
         .into_response()
 }
 
+/// JSON error body shared by every endpoint, see [`with_standard_errors`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status: status.as_u16(),
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, Json(self)).into_response()
+    }
+}
+
+/// Documents the 400, 401, 404 and 500 responses every endpoint can return, all with an
+/// [`ApiError`] body. Chains with other transforms, e.g.
+/// `get_with(handler, |op| with_standard_errors(op.description("...")))`.
+pub fn with_standard_errors(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    op.response_with::<400, Json<ApiError>, _>(|res| res.description("The request was invalid"))
+        .response_with::<401, Json<ApiError>, _>(|res| {
+            res.description("Missing or invalid credentials")
+        })
+        .response_with::<404, Json<ApiError>, _>(|res| {
+            res.description("The resource was not found")
+        })
+        .response_with::<500, Json<ApiError>, _>(|res| res.description("Internal server error"))
+}
+
+/// Name the bearer token scheme is registered under in the OpenAPI components.
+pub const BEARER_AUTH_SCHEME: &str = "bearerAuth";
+
+/// Declares the `Authorization: Bearer <token>` security scheme referenced by [`require_auth`].
+/// [`generate_api_docs_and_serve`] already registers it.
+pub fn register_bearer_auth(api: TransformOpenApi<'_>) -> TransformOpenApi<'_> {
+    api.security_scheme(
+        BEARER_AUTH_SCHEME,
+        SecurityScheme::Http {
+            scheme: "bearer".to_string(),
+            bearer_format: None,
+            description: Some("A bearer token in the Authorization header".to_string()),
+            extensions: Default::default(),
+        },
+    )
+}
+
+/// Marks the operation as needing a bearer token, so the docs show it as protected.
+pub fn require_auth(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    op.security_requirement(BEARER_AUTH_SCHEME)
+}
+
 #[derive(Debug, Error)]
 pub enum ApiServeError {
     #[error("Could not serialize api: {0}")]
@@ -54,7 +116,7 @@ pub async fn generate_api_docs_and_serve(
         .route("/api.json", get(serve_api))
         .route("/swagger", Swagger::new("/api.json").axum_route())
         // Generate the documentation.
-        .finish_api(&mut api)
+        .finish_api_with(&mut api, register_bearer_auth)
         .into_make_service();
 
     // No cached version exists, so we need to serialize and cache it
@@ -4,13 +4,16 @@ use std::str::FromStr;
 use std::{fs, path::Path, str};
 
 macro_rules! static_extensions {
-    ($($variant:ident => $ext_str:expr),* $(,)?) => {
+    ($($variant:ident => $ext_str:expr, $mime_str:expr);* $(;)?) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
         pub enum StaticExtension {
             $($variant),*
         }
 
         impl StaticExtension {
+            /// Every extension, in the order they are defined.
+            pub const ALL: &'static [Self] = &[$(Self::$variant),*];
+
             /// Returns the extension string exactly as it was given.
             pub const fn get_static_str(&self) -> &'static str {
                 match self {
@@ -18,6 +21,13 @@ macro_rules! static_extensions {
                 }
             }
 
+            /// The MIME type objects with this extension should be served with.
+            pub const fn get_mime_type(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $mime_str),*
+                }
+            }
+
             /// Case-sensitive comparison; returns `None` if no match.
             pub fn from_raw_str(s: &str) -> Option<Self> {
                 match s {
@@ -31,11 +41,71 @@ macro_rules! static_extensions {
 
 // ---- Define the extensions you care about here ------------
 static_extensions! {
-    Pdf  => "pdf",
-    Xlsx => "xlsx",
-    Md   => "md",
-    Html => "html",
-    Png  => "png",
+    Pdf  => "pdf",  "application/pdf";
+    Xlsx => "xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+    Md   => "md",   "text/markdown";
+    Html => "html", "text/html";
+    Png  => "png",  "image/png";
+    Json => "json", "application/json";
+    Txt  => "txt",  "text/plain";
+    Csv  => "csv",  "text/csv";
+    Jpg  => "jpg",  "image/jpeg";
+    Jpeg => "jpeg", "image/jpeg";
+    Webp => "webp", "image/webp";
+    Zip  => "zip",  "application/zip";
+    Gz   => "gz",   "application/gzip";
+}
+
+/// Case-insensitive and ignores a leading dot, so `"PDF"`, `".pdf"` and `"pdf"` all match.
+pub fn mime_type_for_extension(ext: &str) -> Option<&'static str> {
+    let ext = ext.trim();
+    let ext = ext.strip_prefix('.').unwrap_or(ext).to_lowercase();
+    StaticExtension::from_raw_str(&ext).map(|ext| ext.get_mime_type())
+}
+
+/// Looks up the MIME type from the extension of the last path segment, works for S3 keys as well
+/// as local paths.
+pub fn mime_type_for_path(path: &str) -> Option<&'static str> {
+    let (_, ext) = file_name(path).rsplit_once('.')?;
+    mime_type_for_extension(ext)
+}
+
+/// The canonical extension for a `Content-Type`, ignoring parameters like `; charset=utf-8`. When
+/// several extensions share a MIME type the first defined one wins, e.g. `jpg` over `jpeg`.
+pub fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    let essence = mime.split(';').next().unwrap_or(mime).trim();
+    StaticExtension::ALL
+        .iter()
+        .find(|ext| ext.get_mime_type().eq_ignore_ascii_case(essence))
+        .map(|ext| ext.get_static_str())
+}
+
+/// Extensions that only make sense together, longest first so `tar.gz` wins over `gz`.
+const COMPOUND_EXTENSIONS: &[&str] = &["tar.bz2", "tar.zst", "tar.gz"];
+
+/// The extension of the last path segment without the leading dot, keeping known compound
+/// extensions like `tar.gz` whole.
+pub fn extension_of(path: &str) -> Option<&str> {
+    let file_name = file_name(path);
+    for compound in COMPOUND_EXTENSIONS {
+        let Some(split) = file_name.len().checked_sub(compound.len()) else {
+            continue;
+        };
+        let is_match = split > 1
+            && file_name.as_bytes()[split - 1] == b'.'
+            && file_name
+                .get(split..)
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(compound));
+        if is_match {
+            return Some(&file_name[split..]);
+        }
+    }
+    let (stem, ext) = file_name.rsplit_once('.')?;
+    (!stem.is_empty() && !ext.is_empty()).then_some(ext)
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
 }
 #[derive(Clone, Copy, Debug)]
 pub enum FileEncoding {
@@ -67,6 +137,12 @@ impl FileExtension {
             _ => FileEncoding::Unknown,
         }
     }
+    pub fn mime_type(&self) -> Option<&'static str> {
+        match self {
+            Self::Static(val) => Some(val.get_mime_type()),
+            _ => None,
+        }
+    }
     fn to_str(&self) -> &str {
         match self {
             Self::Static(val) => val.get_static_str(),
@@ -159,6 +235,69 @@ impl FileEncoding {
     }
 }
 
+/// What a file actually contains, as opposed to what its name claims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    Pdf,
+    Png,
+    Jpeg,
+    Gzip,
+    Zip,
+    Json,
+    Text,
+}
+
+impl FileKind {
+    pub const fn static_extension(&self) -> StaticExtension {
+        match self {
+            Self::Pdf => StaticExtension::Pdf,
+            Self::Png => StaticExtension::Png,
+            Self::Jpeg => StaticExtension::Jpg,
+            Self::Gzip => StaticExtension::Gz,
+            Self::Zip => StaticExtension::Zip,
+            Self::Json => StaticExtension::Json,
+            Self::Text => StaticExtension::Txt,
+        }
+    }
+    pub const fn extension(&self) -> &'static str {
+        self.static_extension().get_static_str()
+    }
+    pub const fn mime_type(&self) -> &'static str {
+        self.static_extension().get_mime_type()
+    }
+}
+
+/// Guesses the file kind from its magic number, falling back to JSON or plain text for valid
+/// UTF-8. Office formats like xlsx are zip archives and are reported as [`FileKind::Zip`].
+pub fn sniff_from_bytes(bytes: &[u8]) -> Option<FileKind> {
+    const SIGNATURES: &[(&[u8], FileKind)] = &[
+        (b"%PDF", FileKind::Pdf),
+        (b"\x89PNG\r\n\x1a\n", FileKind::Png),
+        (b"\xff\xd8\xff", FileKind::Jpeg),
+        (b"\x1f\x8b", FileKind::Gzip),
+        (b"PK\x03\x04", FileKind::Zip),
+        (b"PK\x05\x06", FileKind::Zip),
+    ];
+    if let Some((_, kind)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+    {
+        return Some(*kind);
+    }
+    let text = str::from_utf8(bytes).ok()?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text).trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    let looks_like_json =
+        text.starts_with(['{', '[']) && serde_json::from_str::<de::IgnoredAny>(text).is_ok();
+    Some(if looks_like_json {
+        FileKind::Json
+    } else {
+        FileKind::Text
+    })
+}
+
 use schemars::{JsonSchema, json_schema};
 use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 
@@ -196,3 +335,45 @@ impl JsonSchema for FileExtension {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_from_bytes() {
+        assert_eq!(sniff_from_bytes(b"%PDF-1.7\n"), Some(FileKind::Pdf));
+        assert_eq!(
+            sniff_from_bytes(b"\x89PNG\r\n\x1a\n\0\0"),
+            Some(FileKind::Png)
+        );
+        assert_eq!(sniff_from_bytes(b"\x1f\x8b\x08\0"), Some(FileKind::Gzip));
+        assert_eq!(sniff_from_bytes(b" {\"a\": [1, 2]}"), Some(FileKind::Json));
+        assert_eq!(sniff_from_bytes(b"{not json"), Some(FileKind::Text));
+        assert_eq!(sniff_from_bytes(b"\xff\xfe\x00garbage"), None);
+        assert_eq!(sniff_from_bytes(b""), None);
+        assert_eq!(FileKind::Jpeg.extension(), "jpg");
+    }
+
+    #[test]
+    fn test_extension_for_mime() {
+        assert_eq!(extension_for_mime("application/json"), Some("json"));
+        assert_eq!(extension_for_mime("image/jpeg"), Some("jpg"));
+        assert_eq!(extension_for_mime("Text/HTML; charset=UTF-8"), Some("html"));
+        assert_eq!(extension_for_mime("application/x-unknown"), None);
+        for ext in StaticExtension::ALL {
+            let mime = mime_type_for_extension(ext.get_static_str()).unwrap();
+            assert!(mime_type_for_extension(extension_for_mime(mime).unwrap()) == Some(mime));
+        }
+    }
+
+    #[test]
+    fn test_extension_of() {
+        assert_eq!(extension_of("archives/foo.tar.gz"), Some("tar.gz"));
+        assert_eq!(extension_of("FOO.TAR.ZST"), Some("TAR.ZST"));
+        assert_eq!(extension_of("foo.gz"), Some("gz"));
+        assert_eq!(extension_of("foo"), None);
+        assert_eq!(extension_of("dir.d/foo"), None);
+        assert_eq!(extension_of(".tar.gz"), Some("gz"));
+    }
+}
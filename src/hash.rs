@@ -1,4 +1,7 @@
-use base64::{Engine, engine::general_purpose::URL_SAFE};
+use base64::{
+    Engine,
+    engine::general_purpose::{URL_SAFE, URL_SAFE_NO_PAD},
+};
 use blake2::{Blake2b, Digest};
 use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 use std::borrow::Cow;
@@ -8,9 +11,186 @@ use std::io::{self, Read};
 use std::path::Path;
 use std::str::FromStr;
 use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use schemars::{JsonSchema, Schema, json_schema};
 
+/// Hash functions available through [`hash_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Sha256,
+    /// The fastest option, good for content addressing.
+    Blake3,
+    /// BLAKE2b-256, the same hash as [`Blake2bHash`].
+    Blake2b,
+    /// Only for interop like S3 ETags, it is not collision resistant.
+    Md5,
+    /// A checksum rather than a hash, encoded as 4 big-endian bytes.
+    Crc32c,
+}
+
+impl Algorithm {
+    /// Lowercase name, the same as the serialized form.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+            Self::Blake2b => "blake2b",
+            Self::Md5 => "md5",
+            Self::Crc32c => "crc32c",
+        }
+    }
+
+    /// Length of the digest in bytes.
+    pub const fn output_len(&self) -> usize {
+        match self {
+            Self::Sha256 | Self::Blake3 | Self::Blake2b => 32,
+            Self::Md5 => 16,
+            Self::Crc32c => 4,
+        }
+    }
+}
+
+/// A storage key derived from the data's hash, like `sha256/ab/cd/abcd...`. The two levels of
+/// prefix shards keep any one prefix from growing too large.
+pub fn content_addressed_key(algo: Algorithm, data: &[u8]) -> String {
+    let digest = hash_bytes_hex(algo, data);
+    format!(
+        "{}/{}/{}/{}",
+        algo.as_str(),
+        &digest[..2],
+        &digest[2..4],
+        digest
+    )
+}
+
+/// Incremental version of [`hash_bytes`] for inputs too large to hold in memory, feeding chunks in
+/// any split gives the same digest.
+#[derive(Clone)]
+pub struct Hasher {
+    state: HasherState,
+}
+
+#[derive(Clone)]
+enum HasherState {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Blake2b(Blake2b<blake2::digest::consts::U32>),
+    Md5(md5::Md5),
+    Crc32c(u32),
+}
+
+impl Hasher {
+    pub fn new(algo: Algorithm) -> Self {
+        let state = match algo {
+            Algorithm::Sha256 => HasherState::Sha256(sha2::Sha256::new()),
+            Algorithm::Blake3 => HasherState::Blake3(Box::default()),
+            Algorithm::Blake2b => HasherState::Blake2b(Blake2b::new()),
+            Algorithm::Md5 => HasherState::Md5(md5::Md5::new()),
+            Algorithm::Crc32c => HasherState::Crc32c(0),
+        };
+        Hasher { state }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        match self.state {
+            HasherState::Sha256(_) => Algorithm::Sha256,
+            HasherState::Blake3(_) => Algorithm::Blake3,
+            HasherState::Blake2b(_) => Algorithm::Blake2b,
+            HasherState::Md5(_) => Algorithm::Md5,
+            HasherState::Crc32c(_) => Algorithm::Crc32c,
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        match &mut self.state {
+            HasherState::Sha256(hasher) => hasher.update(chunk),
+            HasherState::Blake3(hasher) => {
+                hasher.update(chunk);
+            }
+            HasherState::Blake2b(hasher) => hasher.update(chunk),
+            HasherState::Md5(hasher) => hasher.update(chunk),
+            HasherState::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, chunk),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self.state {
+            HasherState::Sha256(hasher) => hasher.finalize().to_vec(),
+            HasherState::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            HasherState::Blake2b(hasher) => hasher.finalize().to_vec(),
+            HasherState::Md5(hasher) => hasher.finalize().to_vec(),
+            HasherState::Crc32c(crc) => crc.to_be_bytes().to_vec(),
+        }
+    }
+}
+
+impl fmt::Debug for Hasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hasher")
+            .field("algorithm", &self.algorithm())
+            .finish_non_exhaustive()
+    }
+}
+
+pub fn hash_bytes(algo: Algorithm, data: &[u8]) -> Vec<u8> {
+    let mut hasher = Hasher::new(algo);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Hashes everything `reader` yields without buffering it all.
+pub fn hash_reader<R: Read>(algo: Algorithm, mut reader: R) -> io::Result<Vec<u8>> {
+    let mut hasher = Hasher::new(algo);
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Async version of [`hash_reader`], e.g. for an S3 body turned into an `AsyncRead`.
+pub async fn hash_async_reader<R: AsyncRead + Unpin>(
+    algo: Algorithm,
+    mut reader: R,
+) -> io::Result<Vec<u8>> {
+    let mut hasher = Hasher::new(algo);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let count = reader.read(&mut buffer).await?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Same as [`hash_bytes`] as a lowercase hex string.
+pub fn hash_bytes_hex(algo: Algorithm, data: &[u8]) -> String {
+    to_hex(&hash_bytes(algo, data))
+}
+
+/// Lowercase hex, the encoding used for hashes in S3 keys and logs.
+pub fn to_hex(bytes: &[u8]) -> String {
+    hex::encode(bytes)
+}
+
+/// Parses hex in either case, failing on odd lengths or non hex characters.
+pub fn from_hex(hex_str: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(hex_str)
+}
+
+/// URL-safe base64 without padding, so the result can go straight into a URL or S3 key.
+pub fn to_base64url(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
 /// Represents a base64 URL-encoded BLAKE2b-256 hash
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Blake2bHash([u8; 32]);
@@ -116,6 +296,67 @@ mod tests {
     use super::*;
 
     use hex;
+    #[test]
+    fn test_hash_bytes_known_digests() {
+        let cases = [
+            (
+                Algorithm::Sha256,
+                &b""[..],
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                Algorithm::Blake3,
+                b"",
+                "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            ),
+            (Algorithm::Md5, b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (Algorithm::Crc32c, b"123456789", "e3069283"),
+        ];
+        for (algo, data, expected_hex) in cases {
+            assert_eq!(hash_bytes_hex(algo, data), expected_hex, "{algo:?}");
+            assert_eq!(hash_bytes(algo, data).len(), algo.output_len());
+        }
+    }
+
+    #[test]
+    fn test_encoding_helpers() {
+        let bytes = [0x00, 0xab, 0xff, 0x10];
+        assert_eq!(to_hex(&bytes), "00abff10");
+        assert_eq!(from_hex("00ABff10").unwrap(), bytes);
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+        assert_eq!(to_base64url(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn test_content_addressed_key() {
+        assert_eq!(
+            content_addressed_key(Algorithm::Sha256, b""),
+            "sha256/e3/b0/e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_incremental_hashing_matches_one_shot() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        for algo in [
+            Algorithm::Sha256,
+            Algorithm::Blake3,
+            Algorithm::Blake2b,
+            Algorithm::Md5,
+            Algorithm::Crc32c,
+        ] {
+            let expected = hash_bytes(algo, &data);
+            let mut hasher = Hasher::new(algo);
+            for chunk in data.chunks(7919) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finalize(), expected, "{algo:?}");
+            assert_eq!(hash_reader(algo, &data[..]).unwrap(), expected);
+            assert_eq!(hash_async_reader(algo, &data[..]).await.unwrap(), expected);
+        }
+    }
+
     #[test]
     fn test_kessler_hash_round_trip() {
         let mut rng = rand::rng();
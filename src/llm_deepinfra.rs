@@ -1,12 +1,25 @@
+use futures_util::{Stream, StreamExt, stream};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::env;
 use std::fmt::Debug;
 use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{Instrument, debug, debug_span, warn};
 
-use crate::misc::fmap_empty;
+use crate::misc::retry::RetryPolicy;
+use crate::misc::{fmap_empty, into_fmap_empty};
 
+pub const DEEPINFRA_API_KEY_ENV: &str = "DEEPINFRA_API_KEY";
+
+/// Panics on first access when the env var is unset, use [`DeepInfraClient::from_env`] to get an
+/// error instead.
 pub static DEEPINFRA_API_KEY: LazyLock<String> =
-    LazyLock::new(|| env::var("DEEPINFRA_API_KEY").expect("Expected DEEPINFRA_API_KEY"));
+    LazyLock::new(|| env::var(DEEPINFRA_API_KEY_ENV).expect("Expected DEEPINFRA_API_KEY"));
 
 pub const FAST_CHEAP_MODEL_NAME: &str = "Qwen/Qwen3-32B";
 pub const REASONING_MODEL_NAME: &str = "openai/gpt-oss-120b";
@@ -14,102 +27,1393 @@ pub const REASONING_MODEL_NAME: &str = "openai/gpt-oss-120b";
 #[derive(Debug, thiserror::Error)]
 pub enum DeepInfraError {
     #[error("HTTP request failed: {0}")]
-    Reqwest(#[from] reqwest::Error),
+    Reqwest(reqwest::Error),
+    #[error("Request timed out")]
+    Timeout,
     #[error("Failed to deserialize response: {0}")]
     Serde(#[from] serde_json::Error),
     #[error("API returned an error: {0}")]
     ApiError(String),
+    #[error(transparent)]
+    ApiErrorDetail(ApiErrorDetail),
     #[error("No choices returned from API")]
     NoChoices,
+    #[error("DEEPINFRA_API_KEY is not set")]
+    MissingApiKey,
+    #[error("Model returned invalid JSON: {source}")]
+    InvalidJson {
+        source: serde_json::Error,
+        content: String,
+    },
 }
 
-#[derive(Serialize)]
-struct DeepInfraRequestBody {
-    model: &'static str,
-    messages: Vec<DeepInfraMessage>,
+impl From<reqwest::Error> for DeepInfraError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            DeepInfraError::Timeout
+        } else {
+            DeepInfraError::Reqwest(err)
+        }
+    }
+}
+
+/// The `{"error": {...}}` body returned by OpenAI compatible APIs on failure.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("API returned an error ({status}): {message}")]
+pub struct ApiErrorDetail {
+    pub message: String,
+    pub type_: Option<String>,
+    pub code: Option<String>,
+    pub status: u16,
+}
+
+/// Builds the most specific error possible out of a failed response body, falling back to the raw
+/// text when it isn't a recognisable error object.
+fn parse_api_error(status: u16, body: String) -> DeepInfraError {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(&body) else {
+        return DeepInfraError::ApiError(body);
+    };
+    let as_string = |value: &serde_json::Value| match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(val) => Some(val.clone()),
+        other => Some(other.to_string()),
+    };
+    // DeepInfra occasionally answers with a bare `{"detail": "..."}` instead of an error object.
+    let (error, message) = match (value.get("error"), value.get("detail")) {
+        (Some(error), _) => (error, error.get("message").and_then(as_string)),
+        (None, Some(detail)) => (detail, as_string(detail)),
+        (None, None) => return DeepInfraError::ApiError(body),
+    };
+    let Some(message) = message.or_else(|| error.as_str().map(str::to_string)) else {
+        return DeepInfraError::ApiError(body);
+    };
+    DeepInfraError::ApiErrorDetail(ApiErrorDetail {
+        message,
+        type_: error.get("type").and_then(as_string),
+        code: error.get("code").and_then(as_string),
+        status,
+    })
+}
+
+/// Reads `Retry-After` as either a number of seconds or an HTTP date.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+async fn error_from_response(response: reqwest::Response) -> DeepInfraError {
+    let status = response.status().as_u16();
+    match response.text().await {
+        Ok(body) => parse_api_error(status, body),
+        Err(err) => err.into(),
+    }
+}
+
+/// Root of DeepInfra's OpenAI compatible API, endpoint paths like `/chat/completions` are appended.
+pub const DEEPINFRA_BASE_URL: &str = "https://api.deepinfra.com/v1/openai";
+
+/// Client for any OpenAI compatible chat completions API, defaulting to DeepInfra. Cloning is cheap
+/// and clones share the same connection pool.
+#[derive(Clone)]
+pub struct DeepInfraClient {
+    pub base_url: String,
+    /// When set, 429 and 5xx responses are retried, waiting for `Retry-After` if the server sends
+    /// one and backing off exponentially otherwise.
+    pub retry_policy: Option<RetryPolicy>,
+    /// Default for requests whose [`GenerationParams::timeout`] is unset, covering the whole
+    /// request including reading the body.
+    pub timeout: Option<Duration>,
+    api_key: String,
+    http: reqwest::Client,
+}
+
+impl Debug for DeepInfraClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeepInfraClient")
+            .field("base_url", &self.base_url)
+            .field("retry_policy", &self.retry_policy)
+            .field("timeout", &self.timeout)
+            .field("api_key", &"***")
+            .finish()
+    }
+}
+
+static DEFAULT_CLIENT: LazyLock<Option<DeepInfraClient>> =
+    LazyLock::new(|| DeepInfraClient::from_env().ok());
+
+/// The client behind the module level functions, built from `DEEPINFRA_API_KEY` on first use.
+fn default_client() -> Result<&'static DeepInfraClient, DeepInfraError> {
+    DEFAULT_CLIENT.as_ref().ok_or(DeepInfraError::MissingApiKey)
+}
+
+impl DeepInfraClient {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        DeepInfraClient {
+            base_url: DEEPINFRA_BASE_URL.to_string(),
+            retry_policy: None,
+            timeout: None,
+            api_key: api_key.into(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Reads the API key from `DEEPINFRA_API_KEY`, failing with
+    /// [`DeepInfraError::MissingApiKey`] rather than panicking when it is unset or empty.
+    pub fn from_env() -> Result<Self, DeepInfraError> {
+        match env::var(DEEPINFRA_API_KEY_ENV) {
+            Ok(api_key) if !api_key.is_empty() => Ok(Self::new(api_key)),
+            _ => Err(DeepInfraError::MissingApiKey),
+        }
+    }
+
+    /// Uses an existing `reqwest::Client`, for sharing a connection pool or custom TLS/proxy setup.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Points the client at another provider, e.g. `https://api.groq.com/openai/v1` or a local vLLM
+    /// server.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_retry(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn endpoint_url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    /// POSTs `body` to `path`, applying the retry policy to rate limits and server errors. Any other
+    /// failure status is turned into an error straight away.
+    async fn post_json<B: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        body: &B,
+        timeout: Option<Duration>,
+    ) -> Result<reqwest::Response, DeepInfraError> {
+        let url = self.endpoint_url(path);
+        let mut attempt = 1;
+        loop {
+            let mut request = self
+                .http
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .json(body);
+            if let Some(timeout) = timeout.or(self.timeout) {
+                request = request.timeout(timeout);
+            }
+            let response = request.send().await?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+            let is_retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            match self.retry_policy {
+                Some(policy) if is_retryable && attempt < policy.max_attempts => {
+                    let delay =
+                        retry_after(&response).unwrap_or_else(|| policy.delay_for_attempt(attempt));
+                    warn!(%status, %attempt, ?delay, %url, "Retryable LLM API error, retrying");
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => return Err(error_from_response(response).await),
+            }
+        }
+    }
+
+    pub async fn chat_with_usage(
+        &self,
+        model_name: &str,
+        messages: &[ChatMessage],
+        params: &GenerationParams,
+    ) -> Result<(String, Usage), DeepInfraError> {
+        let request_body = DeepInfraRequestBody {
+            model: model_name,
+            messages,
+            stream: false,
+            tools: &[],
+            tool_choice: None,
+            add_generation_prompt: params.continue_final_message.then_some(false),
+            params,
+        };
+        let (message, usage) = self.complete(&request_body).await?;
+        Ok((message.content.unwrap_or_default(), usage))
+    }
+
+    /// Offers `tools` to the model. When it decides to call them the reply carries
+    /// [`ToolChatResponse::tool_calls`] and usually no content. Append
+    /// [`ToolChatResponse::to_message`] and a [`ChatMessage::tool`] result per call to the history
+    /// before asking again.
+    pub async fn chat_with_tools(
+        &self,
+        model_name: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+        tool_choice: &ToolChoice,
+        params: &GenerationParams,
+    ) -> Result<ToolChatResponse, DeepInfraError> {
+        let request_body = DeepInfraRequestBody {
+            model: model_name,
+            messages,
+            stream: false,
+            tools,
+            tool_choice: Some(tool_choice),
+            add_generation_prompt: params.continue_final_message.then_some(false),
+            params,
+        };
+        let (message, usage) = self.complete(&request_body).await?;
+        Ok(ToolChatResponse {
+            content: into_fmap_empty(message.content),
+            tool_calls: message.tool_calls,
+            usage,
+        })
+    }
+
+    /// Logs the model and message count when the request goes out, and the token usage and
+    /// latency once it returns. Prompt contents are never logged.
+    async fn complete(
+        &self,
+        request_body: &DeepInfraRequestBody<'_>,
+    ) -> Result<(DeepInfraResponseMessage, Usage), DeepInfraError> {
+        let span = debug_span!(
+            "llm_completion",
+            model = %request_body.model,
+            message_count = request_body.messages.len()
+        );
+        self.complete_inner(request_body).instrument(span).await
+    }
+
+    async fn complete_inner(
+        &self,
+        request_body: &DeepInfraRequestBody<'_>,
+    ) -> Result<(DeepInfraResponseMessage, Usage), DeepInfraError> {
+        debug!("Sending LLM completion request");
+        let started = Instant::now();
+        let response = self
+            .post_json(
+                "chat/completions",
+                request_body,
+                request_body.params.timeout,
+            )
+            .await?;
+        let response_body: DeepInfraResponseBody = response.json().await?;
+        let usage = response_body.usage;
+        debug!(
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
+            total_tokens = usage.total_tokens,
+            latency = ?started.elapsed(),
+            "Received LLM completion"
+        );
+        match response_body.choices.into_iter().next() {
+            Some(choice) => Ok((choice.message, usage)),
+            None => Err(DeepInfraError::NoChoices),
+        }
+    }
+
+    pub async fn prompt_with_usage(
+        &self,
+        model_name: &str,
+        system_prompt: Option<&str>,
+        user_prompt: Option<&str>,
+        params: &GenerationParams,
+    ) -> Result<(String, Usage), DeepInfraError> {
+        let messages = build_messages(system_prompt, user_prompt);
+        self.chat_with_usage(model_name, &messages, params).await
+    }
+
+    pub async fn cheap_prompt(&self, sys_prompt: &str) -> Result<String, DeepInfraError> {
+        self.prompt_with_usage(
+            FAST_CHEAP_MODEL_NAME,
+            Some(sys_prompt),
+            None,
+            &GenerationParams::default(),
+        )
+        .await
+        .map(|(content, _)| content)
+    }
+
+    pub async fn reasoning_prompt(&self, sys_prompt: &str) -> Result<String, DeepInfraError> {
+        self.prompt_with_usage(
+            REASONING_MODEL_NAME,
+            Some(sys_prompt),
+            None,
+            &GenerationParams::default(),
+        )
+        .await
+        .map(|(content, _)| content)
+    }
+    /// Same as [`Self::reasoning_prompt`] but keeps the model's `<think>` reasoning in
+    /// [`PromptResult::thinking`] instead of throwing it away.
+    pub async fn reasoning_prompt_detailed(
+        &self,
+        sys_prompt: &str,
+        user_prompt: Option<&str>,
+    ) -> Result<PromptResult, DeepInfraError> {
+        let (content, _) = self
+            .prompt_with_usage(
+                REASONING_MODEL_NAME,
+                Some(sys_prompt),
+                user_prompt,
+                &GenerationParams::default(),
+            )
+            .await?;
+        Ok(split_think(&content))
+    }
+    /// Same as [`Self::cheap_prompt`] but with the request in the user role, which most models
+    /// follow better than a system prompt alone.
+    pub async fn cheap_prompt_user(
+        &self,
+        sys_prompt: &str,
+        user_prompt: &str,
+    ) -> Result<String, DeepInfraError> {
+        self.prompt_with_usage(
+            FAST_CHEAP_MODEL_NAME,
+            Some(sys_prompt),
+            Some(user_prompt),
+            &GenerationParams::default(),
+        )
+        .await
+        .map(|(content, _)| content)
+    }
+    pub async fn reasoning_prompt_user(
+        &self,
+        sys_prompt: &str,
+        user_prompt: &str,
+    ) -> Result<String, DeepInfraError> {
+        self.prompt_with_usage(
+            REASONING_MODEL_NAME,
+            Some(sys_prompt),
+            Some(user_prompt),
+            &GenerationParams::default(),
+        )
+        .await
+        .map(|(content, _)| content)
+    }
+
+    /// Sends the full conversation history and returns the assistant's reply.
+    pub async fn chat(
+        &self,
+        model_name: &str,
+        messages: Vec<ChatMessage>,
+        params: &GenerationParams,
+    ) -> Result<String, DeepInfraError> {
+        self.chat_with_usage(model_name, &messages, params)
+            .await
+            .map(|(content, _)| content)
+    }
+
+    /// Sends `prompt` along with `image_urls` as a single user turn, for OCR and captioning with
+    /// vision models.
+    pub async fn chat_with_images(
+        &self,
+        model_name: &str,
+        system_prompt: Option<&str>,
+        prompt: &str,
+        image_urls: impl IntoIterator<Item = impl Into<String>>,
+        params: &GenerationParams,
+    ) -> Result<String, DeepInfraError> {
+        let mut messages = build_messages(system_prompt, None);
+        messages.push(ChatMessage::user(image_message_parts(prompt, image_urls)));
+        self.chat(model_name, messages, params).await
+    }
+
+    /// Prefills the start of the assistant's reply with `prefill` and has the model continue it,
+    /// a reliable way to coerce the output into a given shape. Returns the prefill followed by the
+    /// continuation.
+    pub async fn chat_continue(
+        &self,
+        model_name: &str,
+        mut messages: Vec<ChatMessage>,
+        prefill: &str,
+        params: &GenerationParams,
+    ) -> Result<String, DeepInfraError> {
+        messages.push(ChatMessage::assistant(prefill));
+        let params = GenerationParams {
+            continue_final_message: true,
+            ..params.clone()
+        };
+        let continuation = self.chat(model_name, messages, &params).await?;
+        Ok(format!("{prefill}{continuation}"))
+    }
+
+    /// Asks for a JSON reply and deserializes it into `T`. JSON object mode is switched on unless
+    /// `params` already sets a [`ResponseFormat`], use [`ResponseFormat::json_schema_for`] to also
+    /// constrain the shape.
+    pub async fn prompt_json<T: DeserializeOwned>(
+        &self,
+        model_name: &str,
+        messages: Vec<ChatMessage>,
+        params: &GenerationParams,
+    ) -> Result<T, DeepInfraError> {
+        let mut params = params.clone();
+        params
+            .response_format
+            .get_or_insert(ResponseFormat::JsonObject);
+        let (content, _) = self.chat_with_usage(model_name, &messages, &params).await?;
+        parse_json_content(&content)
+    }
+
+    /// Embeds each input with an embedding model, returning one vector per input in the same order.
+    pub async fn embed_with_usage(
+        &self,
+        model_name: &str,
+        inputs: &[String],
+    ) -> Result<(Vec<Vec<f32>>, Usage), DeepInfraError> {
+        let request_body = EmbeddingRequestBody {
+            model: model_name,
+            input: inputs,
+        };
+        let response = self.post_json("embeddings", &request_body, None).await?;
+        let mut response_body: EmbeddingResponseBody = response.json().await?;
+        // The API is free to answer out of order, `index` ties each vector back to its input.
+        response_body.data.sort_by_key(|embedding| embedding.index);
+        let embeddings = response_body
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect();
+        Ok((embeddings, response_body.usage))
+    }
+
+    /// Lists the models the OpenAI compatible endpoint serves.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, DeepInfraError> {
+        let mut request = self
+            .http
+            .get(self.endpoint_url("models"))
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+        let response_body: ModelListResponseBody = response.json().await?;
+        Ok(response_body.data)
+    }
+
+    pub async fn embed(
+        &self,
+        model_name: &str,
+        inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, DeepInfraError> {
+        self.embed_with_usage(model_name, inputs)
+            .await
+            .map(|(embeddings, _)| embeddings)
+    }
+
+    /// Runs each prompt as a single user message, with up to `concurrency` requests in flight.
+    /// Results come back in the same order as `prompts`.
+    pub async fn batch_prompt(
+        &self,
+        model_name: &str,
+        prompts: Vec<String>,
+        concurrency: usize,
+    ) -> Vec<Result<String, DeepInfraError>> {
+        let mut results: Vec<Option<Result<String, DeepInfraError>>> =
+            (0..prompts.len()).map(|_| None).collect();
+        let mut completed = stream::iter(prompts.into_iter().enumerate())
+            .map(|(index, prompt)| async move {
+                let result = self
+                    .chat(
+                        model_name,
+                        vec![ChatMessage::user(prompt)],
+                        &GenerationParams::default(),
+                    )
+                    .await;
+                (index, result)
+            })
+            .buffer_unordered(concurrency.max(1));
+        while let Some((index, result)) = completed.next().await {
+            results[index] = Some(result);
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("Every prompt should have completed"))
+            .collect()
+    }
+
+    /// Same as [`Self::chat`] but yields content deltas as the model produces them, ending once the
+    /// server sends `[DONE]`.
+    pub fn chat_stream(
+        &self,
+        model_name: &str,
+        messages: Vec<ChatMessage>,
+        params: &GenerationParams,
+    ) -> impl Stream<Item = Result<String, DeepInfraError>> + use<> {
+        let client = self.clone();
+        let model_name = model_name.to_string();
+        let params = params.clone();
+        stream::once(async move {
+            let request_body = DeepInfraRequestBody {
+                model: &model_name,
+                messages: &messages,
+                stream: true,
+                tools: &[],
+                tool_choice: None,
+                add_generation_prompt: params.continue_final_message.then_some(false),
+                params: &params,
+            };
+            let response = client
+                .post_json("chat/completions", &request_body, params.timeout)
+                .await?;
+            Ok(response.bytes_stream())
+        })
+        .flat_map(|result| match result {
+            Ok(bytes) => sse_content_stream(bytes).left_stream(),
+            Err(err) => stream::once(async { Err(err) }).right_stream(),
+        })
+    }
+}
+
+/// Sampling settings for a completion. Anything left as `None` is omitted from the request so the
+/// server side default applies.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Continue the trailing assistant message instead of starting a new turn, so it acts as a
+    /// prefill of the reply. The returned content only holds the continuation.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub continue_final_message: bool,
+    /// Overrides the client's timeout for this call, reasoning models can need several minutes.
+    /// Not sent to the API.
+    #[serde(skip)]
+    pub timeout: Option<Duration>,
+}
+
+/// Constrains the format of the model's reply, see [`DeepInfraClient::prompt_json`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    pub schema: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+impl ResponseFormat {
+    /// Strict JSON schema mode using the schema generated for `T`.
+    pub fn json_schema_for<T: schemars::JsonSchema>() -> Self {
+        ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat {
+                name: T::schema_name().into_owned(),
+                schema: schemars::schema_for!(T).to_value(),
+                strict: Some(true),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+/// A single turn of a conversation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: MessageContent,
+    /// Calls the assistant made on this turn, see [`DeepInfraClient::chat_with_tools`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Set on [`ChatRole::Tool`] messages to say which call the content answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    fn new(role: ChatRole, content: impl Into<MessageContent>) -> Self {
+        ChatMessage {
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::System, content.into())
+    }
+    pub fn user(content: impl Into<MessageContent>) -> Self {
+        Self::new(ChatRole::User, content)
+    }
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::Assistant, content.into())
+    }
+    /// The result of running the tool call `tool_call_id`.
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        ChatMessage {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(ChatRole::Tool, content.into())
+        }
+    }
+}
+
+/// The body of a [`ChatMessage`], either a plain string or a list of parts for models that
+/// accept images.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ChatContent>),
+}
+
+impl MessageContent {
+    /// The content when it is plain text, `None` for multimodal parts.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            MessageContent::Text(text) => Some(text),
+            MessageContent::Parts(_) => None,
+        }
+    }
+
+    /// The text of the message, with the text parts joined by newlines and images left out.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            MessageContent::Text(text) => Cow::Borrowed(text),
+            MessageContent::Parts(parts) => Cow::Owned(
+                parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        ChatContent::Text(text) => Some(text.as_str()),
+                        ChatContent::ImageUrl(_) => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        }
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        MessageContent::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ChatContent>> for MessageContent {
+    fn from(parts: Vec<ChatContent>) -> Self {
+        MessageContent::Parts(parts)
+    }
+}
+
+/// One part of a multimodal message, serialized in the OpenAI
+/// `{"type": "text", "text": ...}` / `{"type": "image_url", "image_url": {"url": ...}}` shape.
+/// The url may also be a `data:image/...;base64,` uri.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "ContentPart", into = "ContentPart")]
+pub enum ChatContent {
+    Text(String),
+    ImageUrl(String),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrlPart },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ImageUrlPart {
+    url: String,
+}
+
+impl From<ContentPart> for ChatContent {
+    fn from(part: ContentPart) -> Self {
+        match part {
+            ContentPart::Text { text } => ChatContent::Text(text),
+            ContentPart::ImageUrl { image_url } => ChatContent::ImageUrl(image_url.url),
+        }
+    }
+}
+
+impl From<ChatContent> for ContentPart {
+    fn from(content: ChatContent) -> Self {
+        match content {
+            ChatContent::Text(text) => ContentPart::Text { text },
+            ChatContent::ImageUrl(url) => ContentPart::ImageUrl {
+                image_url: ImageUrlPart { url },
+            },
+        }
+    }
+}
+
+/// A function the model may call, serialized in the OpenAI `{"type": "function", ...}` shape.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "function", rename_all = "snake_case")]
+pub enum Tool {
+    Function(FunctionDefinition),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the arguments object.
+    pub parameters: serde_json::Value,
+}
+
+impl Tool {
+    pub fn function(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: serde_json::Value,
+    ) -> Self {
+        Tool::Function(FunctionDefinition {
+            name: name.into(),
+            description: Some(description.into()),
+            parameters,
+        })
+    }
+
+    /// Same as [`Self::function`] with the parameters schema generated from `T`.
+    pub fn function_for<T: schemars::JsonSchema>(
+        name: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        Self::function(name, description, schemars::schema_for!(T).to_value())
+    }
+}
+
+/// Whether the model may, must or must not call tools.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ToolChoice {
+    None,
+    #[default]
+    Auto,
+    Required,
+    /// Forces a call to the named function.
+    Function(String),
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ToolChoice::None => serializer.serialize_str("none"),
+            ToolChoice::Auto => serializer.serialize_str("auto"),
+            ToolChoice::Required => serializer.serialize_str("required"),
+            ToolChoice::Function(name) => {
+                serde_json::json!({"type": "function", "function": {"name": name}})
+                    .serialize(serializer)
+            }
+        }
+    }
+}
+
+/// A call the model wants made. `arguments` is the raw JSON the model generated and isn't
+/// guaranteed to be valid, see [`Self::parse_arguments`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "WireToolCall", into = "WireToolCall")]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+impl ToolCall {
+    pub fn parse_arguments<T: DeserializeOwned>(&self) -> Result<T, DeepInfraError> {
+        serde_json::from_str(&self.arguments).map_err(|source| DeepInfraError::InvalidJson {
+            source,
+            content: self.arguments.clone(),
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize)]
-struct DeepInfraMessage {
-    role: String,
-    content: String,
+struct WireToolCall {
+    id: String,
+    #[serde(rename = "type", default = "function_kind")]
+    kind: String,
+    function: WireFunctionCall,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireFunctionCall {
+    name: String,
+    #[serde(default)]
+    arguments: String,
+}
+
+fn function_kind() -> String {
+    "function".to_string()
+}
+
+impl From<WireToolCall> for ToolCall {
+    fn from(wire: WireToolCall) -> Self {
+        ToolCall {
+            id: wire.id,
+            name: wire.function.name,
+            arguments: wire.function.arguments,
+        }
+    }
+}
+
+impl From<ToolCall> for WireToolCall {
+    fn from(call: ToolCall) -> Self {
+        WireToolCall {
+            id: call.id,
+            kind: function_kind(),
+            function: WireFunctionCall {
+                name: call.name,
+                arguments: call.arguments,
+            },
+        }
+    }
+}
+
+/// Reply to [`DeepInfraClient::chat_with_tools`], holding either content, tool calls or both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolChatResponse {
+    pub content: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: Usage,
+}
+
+impl ToolChatResponse {
+    /// The assistant turn to append to the history before sending the tool results back.
+    pub fn to_message(&self) -> ChatMessage {
+        ChatMessage {
+            tool_calls: self.tool_calls.clone(),
+            ..ChatMessage::assistant(self.content.clone().unwrap_or_default())
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DeepInfraRequestBody<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "<[Tool]>::is_empty")]
+    tools: &'a [Tool],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<&'a ToolChoice>,
+    /// Must be off when continuing the final message, or the chat template opens a fresh turn.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    add_generation_prompt: Option<bool>,
+    #[serde(flatten)]
+    params: &'a GenerationParams,
 }
 
 #[derive(Deserialize)]
 struct DeepInfraResponseBody {
     choices: Vec<DeepInfraChoice>,
-    usage: DeepInfraResponseUsage,
+    usage: Usage,
 }
 
 #[derive(Deserialize)]
 struct DeepInfraChoice {
-    message: DeepInfraMessage,
+    message: DeepInfraResponseMessage,
 }
 
+/// `content` is null when the model answers with tool calls only.
 #[derive(Deserialize)]
-struct DeepInfraResponseUsage {
-    prompt_tokens: u32,
-    completion_tokens: u32,
-    total_tokens: u32,
+struct DeepInfraResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
 }
 
-async fn simple_prompt(
-    model_name: &'static str,
-    system_prompt: Option<&str>,
-    user_prompt: Option<&str>,
-) -> Result<String, DeepInfraError> {
-    let client = reqwest::Client::new();
+#[derive(Deserialize)]
+struct DeepInfraStreamChunk {
+    choices: Vec<DeepInfraStreamChoice>,
+}
 
+#[derive(Deserialize)]
+struct DeepInfraStreamChoice {
+    delta: DeepInfraDelta,
+}
+
+#[derive(Deserialize)]
+struct DeepInfraDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequestBody<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponseBody {
+    data: Vec<EmbeddingData>,
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct ModelListResponseBody {
+    data: Vec<ModelInfo>,
+}
+
+/// A model returned by [`DeepInfraClient::list_models`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub id: String,
+    #[serde(default)]
+    pub owned_by: Option<String>,
+}
+
+/// Token counts reported by the API for a single request. Embedding requests leave
+/// `completion_tokens` at zero.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    #[serde(default)]
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+fn build_messages(system_prompt: Option<&str>, user_prompt: Option<&str>) -> Vec<ChatMessage> {
     let mut messages = Vec::new();
     if let Some(sys_prompt) = fmap_empty(system_prompt) {
-        messages.push(DeepInfraMessage {
-            role: "system".into(),
-            content: sys_prompt.into(),
-        });
+        messages.push(ChatMessage::system(sys_prompt));
     }
     if let Some(usr_prompt) = fmap_empty(user_prompt) {
-        messages.push(DeepInfraMessage {
-            role: "user".into(),
-            content: usr_prompt.into(),
-        });
+        messages.push(ChatMessage::user(usr_prompt));
     }
+    messages
+}
 
-    let request_body = DeepInfraRequestBody {
-        model: model_name,
-        messages,
-    };
+async fn simple_prompt(
+    model_name: &str,
+    system_prompt: Option<&str>,
+    user_prompt: Option<&str>,
+    params: &GenerationParams,
+) -> Result<(String, Usage), DeepInfraError> {
+    default_client()?
+        .prompt_with_usage(model_name, system_prompt, user_prompt, params)
+        .await
+}
+
+/// Sends the full conversation history and returns the assistant's reply.
+pub async fn chat(
+    model_name: &str,
+    messages: Vec<ChatMessage>,
+    params: &GenerationParams,
+) -> Result<String, DeepInfraError> {
+    default_client()?.chat(model_name, messages, params).await
+}
+
+fn image_message_parts(
+    prompt: &str,
+    image_urls: impl IntoIterator<Item = impl Into<String>>,
+) -> Vec<ChatContent> {
+    let mut parts = vec![ChatContent::Text(prompt.to_string())];
+    parts.extend(
+        image_urls
+            .into_iter()
+            .map(|url| ChatContent::ImageUrl(url.into())),
+    );
+    parts
+}
+
+/// See [`DeepInfraClient::chat_with_images`].
+pub async fn chat_with_images(
+    model_name: &str,
+    system_prompt: Option<&str>,
+    prompt: &str,
+    image_urls: impl IntoIterator<Item = impl Into<String>>,
+    params: &GenerationParams,
+) -> Result<String, DeepInfraError> {
+    default_client()?
+        .chat_with_images(model_name, system_prompt, prompt, image_urls, params)
+        .await
+}
+
+/// Continues an assistant prefill, see [`DeepInfraClient::chat_continue`].
+pub async fn chat_continue(
+    model_name: &str,
+    messages: Vec<ChatMessage>,
+    prefill: &str,
+    params: &GenerationParams,
+) -> Result<String, DeepInfraError> {
+    default_client()?
+        .chat_continue(model_name, messages, prefill, params)
+        .await
+}
+
+/// Runs each prompt concurrently, see [`DeepInfraClient::batch_prompt`].
+pub async fn batch_prompt(
+    model_name: &str,
+    prompts: Vec<String>,
+    concurrency: usize,
+) -> Vec<Result<String, DeepInfraError>> {
+    match default_client() {
+        Ok(client) => client.batch_prompt(model_name, prompts, concurrency).await,
+        Err(_) => prompts
+            .iter()
+            .map(|_| Err(DeepInfraError::MissingApiKey))
+            .collect(),
+    }
+}
+
+/// Offers `tools` to the model, see [`DeepInfraClient::chat_with_tools`].
+pub async fn chat_with_tools(
+    model_name: &str,
+    messages: &[ChatMessage],
+    tools: &[Tool],
+    tool_choice: &ToolChoice,
+    params: &GenerationParams,
+) -> Result<ToolChatResponse, DeepInfraError> {
+    default_client()?
+        .chat_with_tools(model_name, messages, tools, tool_choice, params)
+        .await
+}
+
+/// Asks for a JSON reply and deserializes it into `T`, see [`DeepInfraClient::prompt_json`].
+pub async fn prompt_json<T: DeserializeOwned>(
+    model_name: &str,
+    messages: Vec<ChatMessage>,
+    params: &GenerationParams,
+) -> Result<T, DeepInfraError> {
+    default_client()?
+        .prompt_json(model_name, messages, params)
+        .await
+}
+
+/// Embeds each input with an embedding model, returning one vector per input in the same order.
+pub async fn embed(model_name: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, DeepInfraError> {
+    default_client()?.embed(model_name, inputs).await
+}
+
+/// Same as [`embed`] but also returns the token usage.
+pub async fn embed_with_usage(
+    model_name: &str,
+    inputs: &[String],
+) -> Result<(Vec<Vec<f32>>, Usage), DeepInfraError> {
+    default_client()?.embed_with_usage(model_name, inputs).await
+}
+
+pub async fn list_models() -> Result<Vec<ModelInfo>, DeepInfraError> {
+    default_client()?.list_models().await
+}
 
-    let response = client
-        .post("https://api.deepinfra.com/v1/openai/chat/completions")
-        .header("Authorization", format!("Bearer {}", *DEEPINFRA_API_KEY))
-        .json(&request_body)
-        .send()
-        .await?;
+/// Reasoning models still emit their think block in JSON mode, so it is dropped before parsing.
+fn parse_json_content<T: DeserializeOwned>(content: &str) -> Result<T, DeepInfraError> {
+    serde_json::from_str(&strip_think(content)).map_err(|source| DeepInfraError::InvalidJson {
+        source,
+        content: content.to_string(),
+    })
+}
 
-    if !response.status().is_success() {
-        let error_body = response.text().await?;
-        return Err(DeepInfraError::ApiError(error_body));
+/// Same as `simple_prompt` but yields content deltas as the model produces them, ending once the
+/// server sends `[DONE]`.
+pub fn simple_prompt_stream(
+    model_name: &str,
+    system_prompt: Option<&str>,
+    user_prompt: Option<&str>,
+) -> impl Stream<Item = Result<String, DeepInfraError>> + use<> {
+    match default_client() {
+        Ok(client) => client
+            .chat_stream(
+                model_name,
+                build_messages(system_prompt, user_prompt),
+                &GenerationParams::default(),
+            )
+            .left_stream(),
+        Err(err) => stream::once(async { Err(err) }).right_stream(),
     }
+}
 
-    let response_body: DeepInfraResponseBody = response.json().await?;
+enum SseEvent {
+    Content(Result<String, DeepInfraError>),
+    Done,
+}
 
-    if let Some(choice) = response_body.choices.into_iter().next() {
-        Ok(choice.message.content.to_string())
-    } else {
-        Err(DeepInfraError::NoChoices)
+/// Interprets a single line of a chat completion event stream, ignoring anything that isn't a
+/// `data:` line carrying content.
+fn parse_sse_line(line: &str) -> Option<SseEvent> {
+    let payload = line
+        .trim_end_matches(['\r', '\n'])
+        .strip_prefix("data:")?
+        .trim();
+    if payload == "[DONE]" {
+        return Some(SseEvent::Done);
     }
+    let chunk: DeepInfraStreamChunk = match serde_json::from_str(payload) {
+        Ok(chunk) => chunk,
+        Err(err) => return Some(SseEvent::Content(Err(err.into()))),
+    };
+    let content = into_fmap_empty(chunk.choices.into_iter().next()?.delta.content)?;
+    Some(SseEvent::Content(Ok(content)))
+}
+
+/// Turns the raw response body into content deltas, buffering bytes until a full line is available
+/// so events split across chunk boundaries are reassembled.
+fn sse_content_stream<B: AsRef<[u8]>>(
+    bytes: impl Stream<Item = Result<B, reqwest::Error>>,
+) -> impl Stream<Item = Result<String, DeepInfraError>> {
+    let state = (
+        Box::pin(bytes),
+        Vec::<u8>::new(),
+        VecDeque::<Result<String, DeepInfraError>>::new(),
+        false,
+    );
+    stream::unfold(
+        state,
+        |(mut bytes, mut buffer, mut pending, mut done)| async move {
+            loop {
+                if let Some(item) = pending.pop_front() {
+                    return Some((item, (bytes, buffer, pending, done)));
+                }
+                if done {
+                    return None;
+                }
+                match bytes.next().await {
+                    Some(Ok(chunk)) => buffer.extend_from_slice(chunk.as_ref()),
+                    Some(Err(err)) => {
+                        pending.push_back(Err(err.into()));
+                        done = true;
+                        continue;
+                    }
+                    // Treat whatever is left as a final line, servers don't always end on a newline.
+                    None => {
+                        buffer.push(b'\n');
+                        done = true;
+                    }
+                }
+                while let Some(newline) = buffer.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=newline).collect();
+                    match parse_sse_line(&String::from_utf8_lossy(&line)) {
+                        Some(SseEvent::Content(content)) => pending.push_back(content),
+                        Some(SseEvent::Done) => {
+                            done = true;
+                            break;
+                        }
+                        None => {}
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Prompts any DeepInfra model by name with default generation settings.
+pub async fn prompt(
+    model_name: &str,
+    system_prompt: Option<&str>,
+    user_prompt: Option<&str>,
+) -> Result<String, DeepInfraError> {
+    simple_prompt(
+        model_name,
+        system_prompt,
+        user_prompt,
+        &GenerationParams::default(),
+    )
+    .await
+    .map(|(content, _)| content)
+}
+
+pub async fn prompt_with_params(
+    model_name: &str,
+    system_prompt: Option<&str>,
+    user_prompt: Option<&str>,
+    params: &GenerationParams,
+) -> Result<String, DeepInfraError> {
+    simple_prompt(model_name, system_prompt, user_prompt, params)
+        .await
+        .map(|(content, _)| content)
+}
+
+/// Same as [`prompt_with_params`] but also returns the token usage, for cost tracking.
+pub async fn prompt_with_usage(
+    model_name: &str,
+    system_prompt: Option<&str>,
+    user_prompt: Option<&str>,
+    params: &GenerationParams,
+) -> Result<(String, Usage), DeepInfraError> {
+    simple_prompt(model_name, system_prompt, user_prompt, params).await
 }
 
 pub async fn cheap_prompt(sys_prompt: &str) -> Result<String, DeepInfraError> {
-    simple_prompt(FAST_CHEAP_MODEL_NAME, Some(sys_prompt), None).await
+    default_client()?.cheap_prompt(sys_prompt).await
 }
 
 pub async fn reasoning_prompt(sys_prompt: &str) -> Result<String, DeepInfraError> {
-    simple_prompt(REASONING_MODEL_NAME, Some(sys_prompt), None).await
+    default_client()?.reasoning_prompt(sys_prompt).await
+}
+
+pub async fn reasoning_prompt_detailed(
+    sys_prompt: &str,
+    user_prompt: Option<&str>,
+) -> Result<PromptResult, DeepInfraError> {
+    default_client()?
+        .reasoning_prompt_detailed(sys_prompt, user_prompt)
+        .await
 }
 
-pub fn strip_think(input: &str) -> &str {
-    input.split("</think>").last().unwrap_or(input).trim()
+pub async fn cheap_prompt_user(
+    sys_prompt: &str,
+    user_prompt: &str,
+) -> Result<String, DeepInfraError> {
+    default_client()?
+        .cheap_prompt_user(sys_prompt, user_prompt)
+        .await
+}
+
+pub async fn reasoning_prompt_user(
+    sys_prompt: &str,
+    user_prompt: &str,
+) -> Result<String, DeepInfraError> {
+    default_client()?
+        .reasoning_prompt_user(sys_prompt, user_prompt)
+        .await
+}
+
+/// Counts the tokens a piece of text will cost, so prompts can be trimmed to fit a context window
+/// before the request is sent. Any `Fn(&str) -> usize` works, e.g. a wrapped real tokenizer.
+pub trait TokenCounter {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+impl<F: Fn(&str) -> usize> TokenCounter for F {
+    fn count_tokens(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// The [`estimate_tokens`] heuristic as a [`TokenCounter`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EstimatedTokenCounter;
+
+impl TokenCounter for EstimatedTokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+}
+
+/// A rough token estimate of about four characters per token. It is only an estimate, real
+/// counts depend on the model's tokenizer and run higher for code and non-English text.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+const THINK_OPEN: &str = "<think>";
+const THINK_CLOSE: &str = "</think>";
+
+/// Removes every `<think>...</think>` block and trims the rest. An unterminated `<think>` drops
+/// everything after it, and a leading `</think>` with no opening tag (some chat templates open the
+/// block themselves) drops everything before it. Only allocates when there is a block to remove,
+/// the answer around several blocks can't be returned as a single slice of `input`.
+pub fn strip_think(input: &str) -> Cow<'_, str> {
+    if !input.contains(THINK_OPEN) && !input.contains(THINK_CLOSE) {
+        return Cow::Borrowed(input.trim());
+    }
+    Cow::Owned(split_think(input).content)
+}
+
+/// A reply split into the model's reasoning and its final answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptResult {
+    /// The trimmed contents of every `<think>` block joined by blank lines, `None` when the model
+    /// did not think out loud.
+    pub thinking: Option<String>,
+    pub content: String,
+}
+
+/// Splits `input` the same way [`strip_think`] does, but keeps what was inside the think blocks.
+pub fn split_think(input: &str) -> PromptResult {
+    let mut thinking = Vec::new();
+    let first_open = input.find(THINK_OPEN);
+    let mut rest = match input.find(THINK_CLOSE) {
+        Some(close) if first_open.is_none_or(|open| close < open) => {
+            thinking.push(&input[..close]);
+            &input[close + THINK_CLOSE.len()..]
+        }
+        _ => input,
+    };
+    let mut content = String::with_capacity(rest.len());
+    while let Some(open) = rest.find(THINK_OPEN) {
+        content.push_str(&rest[..open]);
+        let block = &rest[open + THINK_OPEN.len()..];
+        match block.find(THINK_CLOSE) {
+            Some(close) => {
+                thinking.push(&block[..close]);
+                rest = &block[close + THINK_CLOSE.len()..];
+            }
+            None => {
+                thinking.push(block);
+                rest = "";
+            }
+        }
+    }
+    content.push_str(rest);
+    let thinking = thinking
+        .into_iter()
+        .map(str::trim)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    PromptResult {
+        thinking: into_fmap_empty(Some(thinking)),
+        content: content.trim().to_string(),
+    }
 }
 
 pub async fn test_deepinfra() -> Result<String, String> {
@@ -117,3 +1421,160 @@ pub async fn test_deepinfra() -> Result<String, String> {
         .await
         .map_err(|e| e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multimodal_content_serialization() {
+        let message = ChatMessage::user(image_message_parts(
+            "Transcribe this page",
+            ["https://example.com/page.png"],
+        ));
+        let value = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "Transcribe this page"},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/page.png"}},
+                ],
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<ChatMessage>(value).unwrap(),
+            message
+        );
+        assert_eq!(message.content.text(), "Transcribe this page");
+        assert_eq!(message.content.as_text(), None);
+
+        assert_eq!(ChatMessage::user("hi").content.as_text(), Some("hi"));
+        let plain = serde_json::to_value(ChatMessage::user("hi")).unwrap();
+        assert_eq!(plain, serde_json::json!({"role": "user", "content": "hi"}));
+    }
+
+    #[test]
+    fn test_parse_api_error() {
+        let body = r#"{"error": {"message": "Rate limited", "type": "rate_limit", "code": 429}}"#;
+        let DeepInfraError::ApiErrorDetail(detail) = parse_api_error(429, body.to_string()) else {
+            panic!("Expected a structured error");
+        };
+        assert_eq!(
+            detail,
+            ApiErrorDetail {
+                message: "Rate limited".into(),
+                type_: Some("rate_limit".into()),
+                code: Some("429".into()),
+                status: 429,
+            }
+        );
+
+        let DeepInfraError::ApiError(raw) = parse_api_error(502, "Bad Gateway".to_string()) else {
+            panic!("Expected the raw body");
+        };
+        assert_eq!(raw, "Bad Gateway");
+    }
+
+    #[test]
+    fn test_parse_json_content() {
+        let parsed: serde_json::Value =
+            parse_json_content("<think>\nhmm\n</think>\n{\"name\": \"Ada\"}").unwrap();
+        assert_eq!(parsed, serde_json::json!({"name": "Ada"}));
+
+        let Err(DeepInfraError::InvalidJson { content, .. }) =
+            parse_json_content::<serde_json::Value>("Sure! Here is the JSON")
+        else {
+            panic!("Expected an invalid JSON error");
+        };
+        assert_eq!(content, "Sure! Here is the JSON");
+    }
+
+    #[test]
+    fn test_strip_think() {
+        assert_eq!(strip_think("  plain answer \n"), "plain answer");
+        assert_eq!(strip_think("<think>hmm</think>\nanswer"), "answer");
+        assert_eq!(
+            strip_think("<think>a</think>first <think>b</think>second"),
+            "first second"
+        );
+        assert_eq!(strip_think("answer <think>never closed"), "answer");
+        assert_eq!(strip_think("implicit reasoning</think>answer"), "answer");
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(EstimatedTokenCounter.count_tokens("ééééé"), 2);
+        let words = |text: &str| text.split_whitespace().count();
+        assert_eq!(words.count_tokens("three little words"), 3);
+    }
+
+    #[test]
+    fn test_split_think() {
+        assert_eq!(
+            split_think("<think> a </think>first <think>b</think>second"),
+            PromptResult {
+                thinking: Some("a\n\nb".to_string()),
+                content: "first second".to_string(),
+            }
+        );
+        assert_eq!(
+            split_think("implicit reasoning</think>answer").thinking,
+            Some("implicit reasoning".to_string())
+        );
+        assert_eq!(split_think("plain answer").thinking, None);
+    }
+
+    #[test]
+    fn test_tool_call_response_parsing() {
+        let body = r#"{
+            "choices": [{"message": {"role": "assistant", "content": null, "tool_calls": [
+                {"id": "call_1", "type": "function",
+                 "function": {"name": "lookup_docket", "arguments": "{\"id\": 42}"}}
+            ]}}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        }"#;
+        let response: DeepInfraResponseBody = serde_json::from_str(body).unwrap();
+        let message = &response.choices[0].message;
+        assert_eq!(message.content, None);
+        assert_eq!(
+            message.tool_calls,
+            vec![ToolCall {
+                id: "call_1".into(),
+                name: "lookup_docket".into(),
+                arguments: r#"{"id": 42}"#.into(),
+            }]
+        );
+        let arguments: serde_json::Value = message.tool_calls[0].parse_arguments().unwrap();
+        assert_eq!(arguments["id"], 42);
+        assert_eq!(
+            serde_json::to_value(&message.tool_calls[0]).unwrap()["function"]["name"],
+            "lookup_docket"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_reassembles_split_lines() {
+        let chunks: Vec<Result<Vec<u8>, reqwest::Error>> = vec![
+            Ok(b"data: {\"choices\":[{\"delta\":{\"content\":\"Hel".to_vec()),
+            Ok(
+                b"lo\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\" world\"}}]}\n"
+                    .to_vec(),
+            ),
+            Ok(b": keep-alive\n\ndata: {\"choices\":[{\"delta\":{}}]}\n".to_vec()),
+            Ok(
+                b"data: [DONE]\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"ignored\"}}]}\n"
+                    .to_vec(),
+            ),
+        ];
+        let deltas: Vec<String> = sse_content_stream(stream::iter(chunks))
+            .map(|delta| delta.unwrap())
+            .collect()
+            .await;
+        assert_eq!(deltas, vec!["Hello", " world"]);
+    }
+}
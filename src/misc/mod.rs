@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 pub mod internet_check;
+pub mod retry;
 
 pub fn prettyprint_duration(dur: Duration) -> String {
     let total_secs = dur.as_secs();
@@ -36,6 +37,19 @@ pub fn is_env_var_true(var_name: &str) -> bool {
     true
 }
 
+/// Truncates `s` to at most `max_bytes` bytes, backing off to the nearest char boundary so it
+/// never splits a multi-byte character.
+pub fn truncate_str(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 pub trait IsEmpty {
     fn is_empty(&self) -> bool;
 }
@@ -89,3 +103,53 @@ pub fn into_fmap_empty<T: IsEmpty>(val: Option<T>) -> Option<T> {
         },
     }
 }
+
+/// Returns the first `Some` in `opts`, without looking at the rest.
+pub fn first_some<T>(opts: impl IntoIterator<Item = Option<T>>) -> Option<T> {
+    opts.into_iter().flatten().next()
+}
+
+/// Awaits each fallback in order and returns the first success. Futures are lazy, so the later
+/// fallbacks never run once one succeeds. If they all fail, every error is returned in order.
+pub async fn first_ok<T, E, Fut>(ops: impl IntoIterator<Item = Fut>) -> Result<T, Vec<E>>
+where
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut errors = Vec::new();
+    for op in ops {
+        match op.await {
+            Ok(value) => return Ok(value),
+            Err(err) => errors.push(err),
+        }
+    }
+    Err(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_str_respects_char_boundaries() {
+        assert_eq!(truncate_str("hello", 10), "hello");
+        assert_eq!(truncate_str("hello", 3), "hel");
+        // "é" is 2 bytes and "€" is 3, so the limits below land inside them.
+        assert_eq!(truncate_str("aé", 2), "a");
+        assert_eq!(truncate_str("ab€cd", 3), "ab");
+        assert_eq!(truncate_str("ab€cd", 4), "ab");
+        assert_eq!(truncate_str("ab€cd", 5), "ab€");
+        assert_eq!(truncate_str("€", 0), "");
+    }
+
+    #[tokio::test]
+    async fn test_first_some_and_first_ok() {
+        assert_eq!(first_some([None, Some(2), Some(3)]), Some(2));
+        assert_eq!(first_some::<u8>([None, None]), None);
+
+        let attempt = |result: Result<u8, &'static str>| async move { result };
+        let ok = first_ok([attempt(Err("primary")), attempt(Ok(1)), attempt(Ok(2))]).await;
+        assert_eq!(ok, Ok(1));
+        let all_failed = first_ok([attempt(Err("primary")), attempt(Err("fallback"))]).await;
+        assert_eq!(all_failed, Err(vec!["primary", "fallback"]));
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use tokio::time::sleep;
+use tracing::debug;
+
+/// How many times to attempt an operation and how long to back off between attempts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one, so 1 means never retry.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Randomize each delay to somewhere between half and all of the computed backoff, which keeps
+    /// many clients from retrying in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay to wait after `attempt` (starting at 1) has failed.
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .base_delay
+            .saturating_mul(1u32 << exponent)
+            .min(self.max_delay);
+        if self.jitter {
+            backoff.mul_f64(0.5 + rand::random::<f64>() * 0.5)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Runs `op` until it succeeds or `policy.max_attempts` is used up, backing off between attempts.
+/// Returns the error from the last attempt.
+pub async fn retry<F, Fut, T, E>(policy: RetryPolicy, op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    retry_if(policy, |_| true, op).await
+}
+
+/// Same as [`retry`] but gives up straight away on errors `is_retryable` rejects. The predicate is
+/// only consulted when another attempt is left, so it doubles as a place to log the retry.
+pub async fn retry_if<F, Fut, T, E, P>(
+    policy: RetryPolicy,
+    mut is_retryable: P,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+    P: FnMut(&E) -> bool,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < policy.max_attempts && is_retryable(&err) => {
+                let delay = policy.delay_for_attempt(attempt);
+                debug!(%err, %attempt, ?delay, "Attempt failed, retrying");
+                sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn test_retry_if_stops_on_success_or_permanent_errors() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            jitter: false,
+        };
+        let calls = Cell::new(0);
+        let result: Result<u32, String> = retry(policy, || async {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err("transient".to_string())
+            } else {
+                Ok(calls.get())
+            }
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+
+        calls.set(0);
+        let result: Result<(), String> = retry_if(
+            policy,
+            |err: &String| err != "permanent",
+            || async {
+                calls.set(calls.get() + 1);
+                Err("permanent".to_string())
+            },
+        )
+        .await;
+        assert_eq!(result, Err("permanent".to_string()));
+        assert_eq!(calls.get(), 1);
+    }
+}
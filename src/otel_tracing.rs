@@ -1,4 +1,9 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::env;
+use std::fmt::{self, Debug};
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::time::Duration;
 
 #[cfg(feature = "aide")]
 use aide::axum::ApiRouter;
@@ -6,27 +11,397 @@ use aide::axum::ApiRouter;
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
 #[cfg(feature = "otel")]
 use init_tracing_opentelemetry::{
-    otlp::OtelGuard, tracing_subscriber_ext::init_subscribers_and_loglevel,
+    init_propagator,
+    otlp::OtelGuard,
+    tracing_subscriber_ext::{
+        build_level_filter_layer, build_logger_text, init_subscribers_and_loglevel,
+    },
 };
-use tracing::info;
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry::{KeyValue, global};
+use opentelemetry_otlp::tonic_types::metadata::MetadataMap;
+use opentelemetry_otlp::{
+    MetricExporter, SpanExporter, WithExportConfig, WithHttpConfig, WithTonicConfig,
+};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tracing::{info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// `<crate name>-<host name>`, used when no service name is configured.
+fn default_service_name() -> String {
+    // a) Crate name – compile‑time constant, always available.
+    let crate_name = env!("CARGO_PKG_NAME");
+
+    // b) Host name – use the `hostname` crate.
+    //    If it fails for any reason we fall back to "unknown".
+    let host = hostname::get()
+        .ok()
+        .and_then(|os_str| os_str.into_string().ok())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    format!("{crate_name}-{host}")
+}
+
+/// Wire protocol used to talk to the OTLP collector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OtlpProtocol {
+    /// Usually served on port 4317.
+    #[default]
+    Grpc,
+    /// Usually served on port 4318. The `/v1/traces` or `/v1/metrics` path is added to the endpoint
+    /// for each signal, replacing one that is already there.
+    HttpProtobuf,
+}
+
+/// Exporter settings for [`init`], for when configuring through the `OTEL_*` environment variables
+/// isn't an option.
+#[derive(Clone)]
+pub struct OtelConfig {
+    pub endpoint: String,
+    pub protocol: OtlpProtocol,
+    /// Sent with every export, e.g. an `authorization` header for a collector behind auth.
+    pub headers: Vec<(String, String)>,
+    pub service_name: String,
+    /// Defaults to this crate's version, binaries should pass their own
+    /// `env!("CARGO_PKG_VERSION")`.
+    pub service_version: String,
+    /// Exported as `deployment.environment`, e.g. `production` or `staging`.
+    pub environment: Option<String>,
+    /// Extra attributes added to the OTel resource alongside the ones above.
+    pub resource_attributes: Vec<(String, String)>,
+    /// Fraction of traces to export, from `0.0` to `1.0`. Only decides for root spans, child spans
+    /// follow their parent's decision so traces are never exported partially. `None` samples
+    /// everything.
+    pub sample_ratio: Option<f64>,
+}
+
+impl Debug for OtelConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header_names: Vec<&str> = self.headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("OtelConfig")
+            .field("endpoint", &self.endpoint)
+            .field("protocol", &self.protocol)
+            .field("headers", &header_names)
+            .field("service_name", &self.service_name)
+            .field("service_version", &self.service_version)
+            .field("environment", &self.environment)
+            .field("resource_attributes", &self.resource_attributes)
+            .field("sample_ratio", &self.sample_ratio)
+            .finish()
+    }
+}
+
+impl OtelConfig {
+    pub fn new(endpoint: impl Into<String>, protocol: OtlpProtocol) -> Self {
+        OtelConfig {
+            endpoint: endpoint.into(),
+            protocol,
+            headers: Vec::new(),
+            service_name: default_service_name(),
+            service_version: env!("CARGO_PKG_VERSION").to_string(),
+            environment: None,
+            resource_attributes: Vec::new(),
+            sample_ratio: None,
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_bearer_token(self, token: &str) -> Self {
+        self.with_header("authorization", format!("Bearer {token}"))
+    }
+
+    pub fn with_service_name(mut self, service_name: impl Into<String>) -> Self {
+        self.service_name = service_name.into();
+        self
+    }
+
+    pub fn with_service_version(mut self, service_version: impl Into<String>) -> Self {
+        self.service_version = service_version.into();
+        self
+    }
+
+    pub fn with_environment(mut self, environment: impl Into<String>) -> Self {
+        self.environment = Some(environment.into());
+        self
+    }
+
+    pub fn with_resource_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.resource_attributes.push((key.into(), value.into()));
+        self
+    }
+
+    fn resource(&self) -> Resource {
+        let mut attributes = vec![KeyValue::new(
+            "service.version",
+            self.service_version.clone(),
+        )];
+        if let Some(environment) = &self.environment {
+            attributes.push(KeyValue::new("deployment.environment", environment.clone()));
+        }
+        attributes.extend(
+            self.resource_attributes
+                .iter()
+                .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+        );
+        Resource::builder()
+            .with_service_name(self.service_name.clone())
+            .with_attributes(attributes)
+            .build()
+    }
+
+    pub fn with_sample_ratio(mut self, sample_ratio: f64) -> Self {
+        self.sample_ratio = Some(sample_ratio);
+        self
+    }
+
+    fn sampler(&self) -> Sampler {
+        let root_sampler = match self.sample_ratio {
+            Some(ratio) => Sampler::TraceIdRatioBased(ratio.clamp(0.0, 1.0)),
+            None => Sampler::AlwaysOn,
+        };
+        Sampler::ParentBased(Box::new(root_sampler))
+    }
+}
+
+fn grpc_metadata(config: &OtelConfig) -> anyhow::Result<MetadataMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+    }
+    Ok(MetadataMap::from_headers(headers))
+}
+
+/// Unlike gRPC, OTLP over HTTP has a separate path per signal.
+fn http_signal_endpoint(endpoint: &str, signal: &str) -> String {
+    let base = endpoint.trim_end_matches('/');
+    let base = ["/v1/traces", "/v1/metrics"]
+        .iter()
+        .find_map(|path| base.strip_suffix(path))
+        .unwrap_or(base);
+    format!("{base}/v1/{signal}")
+}
+
+fn build_span_exporter(config: &OtelConfig) -> anyhow::Result<SpanExporter> {
+    let exporter = match config.protocol {
+        OtlpProtocol::Grpc => SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(&config.endpoint)
+            .with_metadata(grpc_metadata(config)?)
+            .build()?,
+        OtlpProtocol::HttpProtobuf => SpanExporter::builder()
+            .with_http()
+            .with_endpoint(http_signal_endpoint(&config.endpoint, "traces"))
+            .with_headers(config.headers.iter().cloned().collect())
+            .build()?,
+    };
+    Ok(exporter)
+}
+
+fn build_metric_exporter(config: &OtelConfig) -> anyhow::Result<MetricExporter> {
+    let exporter = match config.protocol {
+        OtlpProtocol::Grpc => MetricExporter::builder()
+            .with_tonic()
+            .with_endpoint(&config.endpoint)
+            .with_metadata(grpc_metadata(config)?)
+            .build()?,
+        OtlpProtocol::HttpProtobuf => MetricExporter::builder()
+            .with_http()
+            .with_endpoint(http_signal_endpoint(&config.endpoint, "metrics"))
+            .with_headers(config.headers.iter().cloned().collect())
+            .build()?,
+    };
+    Ok(exporter)
+}
+
+/// How long [`shutdown`] waits for the last spans to be exported.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The provider installed by [`init`], kept so [`shutdown`] can be called from anywhere.
+static TRACER_PROVIDER: Mutex<Option<SdkTracerProvider>> = Mutex::new(None);
+
+/// The provider installed by [`init_metrics`].
+static METER_PROVIDER: Mutex<Option<SdkMeterProvider>> = Mutex::new(None);
+
+/// Flushes buffered spans and metrics and shuts down the pipelines started by [`init`] and
+/// [`init_metrics`], blocking for at most [`SHUTDOWN_TIMEOUT`] each. Both are exported in batches,
+/// so this must be called before the process exits or the final batch is lost. Dropping the
+/// [`TracingGuard`] and [`MetricsGuard`] shuts down their own halves, later calls do nothing.
+pub fn shutdown() {
+    shutdown_tracing();
+    shutdown_metrics();
+}
+
+fn shutdown_tracing() {
+    let provider = TRACER_PROVIDER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    let Some(provider) = provider else {
+        return;
+    };
+    if let Err(err) = provider.shutdown_with_timeout(SHUTDOWN_TIMEOUT) {
+        warn!(%err, "Failed to flush and shut down the tracer provider");
+    }
+}
+
+fn shutdown_metrics() {
+    let provider = METER_PROVIDER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    let Some(provider) = provider else {
+        return;
+    };
+    if let Err(err) = provider.shutdown_with_timeout(SHUTDOWN_TIMEOUT) {
+        warn!(%err, "Failed to flush and shut down the meter provider");
+    }
+}
+
+/// Returned by [`init`], flushes and shuts down the tracer provider when dropped, leaving metrics
+/// to their own [`MetricsGuard`]. Hold it until the end of `main`.
+#[must_use = "dropping the guard shuts tracing down straight away"]
+pub struct TracingGuard {
+    tracer_provider: SdkTracerProvider,
+}
+
+impl TracingGuard {
+    pub fn tracer_provider(&self) -> &SdkTracerProvider {
+        &self.tracer_provider
+    }
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        shutdown_tracing();
+    }
+}
+
+/// Returned by [`init_metrics`], flushes and shuts down the meter provider when dropped.
+#[must_use = "dropping the guard shuts metrics down straight away"]
+pub struct MetricsGuard {
+    meter_provider: SdkMeterProvider,
+}
+
+impl MetricsGuard {
+    pub fn meter_provider(&self) -> &SdkMeterProvider {
+        &self.meter_provider
+    }
+}
+
+impl Drop for MetricsGuard {
+    fn drop(&mut self) {
+        shutdown_metrics();
+    }
+}
+
+/// Name of the meter used by [`increment_counter`] and [`record_histogram`].
+pub const METER_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Exports metrics to the same collector as traces, with the same headers and resource. Can be used
+/// with or without [`init`].
+pub fn init_metrics(config: &OtelConfig) -> anyhow::Result<MetricsGuard> {
+    let exporter = build_metric_exporter(config)?;
+    let meter_provider = SdkMeterProvider::builder()
+        .with_resource(config.resource())
+        .with_periodic_exporter(exporter)
+        .build();
+    global::set_meter_provider(meter_provider.clone());
+    COUNTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+    HISTOGRAMS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+    *METER_PROVIDER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some(meter_provider.clone());
+    Ok(MetricsGuard { meter_provider })
+}
+
+/// Instruments built by [`increment_counter`] and [`record_histogram`], so hot paths don't rebuild
+/// them on every call. Cleared by [`init_metrics`], anything built before it is a no-op instrument.
+static COUNTERS: LazyLock<Mutex<HashMap<Cow<'static, str>, Counter<u64>>>> =
+    LazyLock::new(Default::default);
+static HISTOGRAMS: LazyLock<Mutex<HashMap<Cow<'static, str>, Histogram<f64>>>> =
+    LazyLock::new(Default::default);
+
+/// Adds `value` to the counter called `name`, e.g. `increment_counter("s3.uploads", 1, &[])`. Does
+/// nothing until [`init_metrics`] has been called.
+pub fn increment_counter(name: impl Into<Cow<'static, str>>, value: u64, attributes: &[KeyValue]) {
+    COUNTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(name.into())
+        .or_insert_with_key(|name| global::meter(METER_NAME).u64_counter(name.clone()).build())
+        .add(value, attributes);
+}
+
+/// Records `value` in the histogram called `name`, e.g. token usage per LLM request.
+pub fn record_histogram(name: impl Into<Cow<'static, str>>, value: f64, attributes: &[KeyValue]) {
+    HISTOGRAMS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(name.into())
+        .or_insert_with_key(|name| {
+            global::meter(METER_NAME)
+                .f64_histogram(name.clone())
+                .build()
+        })
+        .record(value, attributes);
+}
+
+/// Installs the global tracing subscriber, exporting spans to the collector described by `config`
+/// and logging the same way as [`initialize_tracing_and_wrap_router`].
+pub fn init(config: &OtelConfig) -> anyhow::Result<TracingGuard> {
+    let exporter = build_span_exporter(config)?;
+    let sampler = config.sampler();
+    let resource = config.resource();
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_resource(resource)
+        .with_sampler(sampler)
+        .with_batch_exporter(exporter)
+        .build();
+    init_propagator()?;
+    global::set_tracer_provider(tracer_provider.clone());
+    *TRACER_PROVIDER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some(tracer_provider.clone());
+
+    let otel_layer = tracing_opentelemetry::layer()
+        .with_error_records_to_exceptions(true)
+        .with_tracer(tracer_provider.tracer(""));
+    let subscriber = tracing_subscriber::registry()
+        .with(otel_layer)
+        .with(build_level_filter_layer("")?)
+        .with(build_logger_text());
+    tracing::subscriber::set_global_default(subscriber)?;
+    info!(endpoint = %config.endpoint, protocol = ?config.protocol, "Tracing Subscriber is up and running");
+    Ok(TracingGuard { tracer_provider })
+}
 
 #[cfg(feature = "otel")]
 pub fn initialize_tracing_and_wrap_router(
     make_api: impl FnOnce() -> ApiRouter,
 ) -> anyhow::Result<(ApiRouter, OtelGuard)> {
     if env::var("OTEL_SERVICE_NAME").is_err() {
-        // a) Crate name – compile‑time constant, always available.
-        let crate_name = env!("CARGO_PKG_NAME");
-
-        // c) Host name – use the `hostname` crate.
-        //    If it fails for any reason we fall back to "unknown".
-        let host = hostname::get()
-            .ok()
-            .and_then(|os_str| os_str.into_string().ok())
-            .unwrap_or_else(|| "unknown".to_string());
-
-        // d) Assemble the final string.
-        let generated = format!("{crate_name}-{host}");
+        let generated = default_service_name();
         println!("OTEL_SERVICE_NAME was not set, defaulting to {generated}");
 
         // this is only used in a single threaded context upon initialization, so its fine.
@@ -1,4 +1,17 @@
+use std::time::Duration;
+
 use aws_sdk_s3::Client;
+use futures_util::{StreamExt, stream};
+#[cfg(feature = "rkyv")]
+use rkyv::{
+    Archive, Serialize,
+    api::high::{HighSerializer, HighValidator},
+    bytecheck::CheckBytes,
+    de::Pool,
+    rancor::Strategy,
+    ser::allocator::ArenaHandle,
+    util::AlignedVec,
+};
 
 use crate::s3_generic::{
     S3Credentials, fetchers_and_getters::S3Addr, s3_uri::S3LocationWithCredentials,
@@ -15,40 +28,123 @@ pub fn get_openscrapers_json_key<T: CannonicalS3ObjectLocation>(addr: &T::Addres
     T::generate_object_key(addr) + ".json"
 }
 
+#[cfg(feature = "rkyv")]
+pub fn get_openscrapers_rkyv_key<T: CannonicalS3ObjectLocation>(addr: &T::AddressInfo) -> String {
+    T::generate_object_key(addr) + ".rkyv"
+}
+
 pub fn get_s3_json_uri<T: CannonicalS3ObjectLocation>(addr: &T::AddressInfo) -> String {
-    let bucket = T::generate_bucket(&addr);
+    let bucket = T::generate_bucket(addr);
     let key = get_openscrapers_json_key::<T>(addr);
     let credentials = T::get_credentials(addr);
     S3LocationWithCredentials::from_key_bucket_and_credentials(&key, bucket, credentials)
         .to_string()
 }
 
+/// Like [`get_s3_json_uri`], but presigned so the link also works for private buckets until it
+/// expires.
+pub async fn get_s3_presigned_json_uri<T: CannonicalS3ObjectLocation>(
+    addr: &T::AddressInfo,
+    expires_in: Duration,
+) -> anyhow::Result<String> {
+    let bucket = T::generate_bucket(addr);
+    let key = get_openscrapers_json_key::<T>(addr);
+    let credentials = T::get_credentials(addr);
+    S3LocationWithCredentials::from_key_bucket_and_credentials(&key, bucket, credentials)
+        .presigned_url(expires_in)
+        .await
+}
+
 pub async fn download_openscrapers_object<T: CannonicalS3ObjectLocation>(
     s3_client: &Client,
     addr: &T::AddressInfo,
 ) -> anyhow::Result<T> {
     let key = get_openscrapers_json_key::<T>(addr);
-    let bucket = T::generate_bucket(&addr);
+    let bucket = T::generate_bucket(addr);
     S3Addr::new(s3_client, bucket, &key).download_json().await
 }
 
+/// Downloads the objects for every address in `addrs`, keeping at most `concurrency` requests in
+/// flight. Results come back in the same order as `addrs`.
+pub async fn download_openscrapers_objects<T: CannonicalS3ObjectLocation>(
+    s3_client: &Client,
+    addrs: &[T::AddressInfo],
+    concurrency: usize,
+) -> Vec<anyhow::Result<T>> {
+    stream::iter(addrs)
+        .map(|addr| download_openscrapers_object::<T>(s3_client, addr))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
 pub async fn upload_object<T: CannonicalS3ObjectLocation>(
     s3_client: &Client,
     addr: &T::AddressInfo,
     object: &T,
 ) -> anyhow::Result<()> {
     let key = get_openscrapers_json_key::<T>(addr);
-    let bucket = T::generate_bucket(&addr);
+    let bucket = T::generate_bucket(addr);
     S3Addr::new(s3_client, bucket, &key)
         .upload_json(&object)
         .await
 }
 
+/// Same as [`download_openscrapers_object`] but reads the `.rkyv` copy of the object.
+#[cfg(feature = "rkyv")]
+pub async fn download_openscrapers_object_rkyv<T>(
+    s3_client: &Client,
+    addr: &T::AddressInfo,
+) -> anyhow::Result<T>
+where
+    T: CannonicalS3ObjectLocation + Archive,
+    T::Archived: for<'b> CheckBytes<HighValidator<'b, rkyv::rancor::Error>>
+        + rkyv::Deserialize<T, Strategy<Pool, rkyv::rancor::Error>>,
+{
+    let key = get_openscrapers_rkyv_key::<T>(addr);
+    let bucket = T::generate_bucket(addr);
+    S3Addr::new(s3_client, bucket, &key).download_rkyv().await
+}
+
+/// Same as [`upload_object`] but stores the object as `.rkyv` next to where the json would live.
+#[cfg(feature = "rkyv")]
+pub async fn upload_object_rkyv<T>(
+    s3_client: &Client,
+    addr: &T::AddressInfo,
+    object: &T,
+) -> anyhow::Result<()>
+where
+    T: CannonicalS3ObjectLocation
+        + Archive
+        + for<'b> Serialize<HighSerializer<AlignedVec, ArenaHandle<'b>, rkyv::rancor::Error>>,
+{
+    let key = get_openscrapers_rkyv_key::<T>(addr);
+    let bucket = T::generate_bucket(addr);
+    S3Addr::new(s3_client, bucket, &key)
+        .upload_rkyv(object)
+        .await
+}
+
+/// Same as [`upload_object`] but skips the upload when the stored object is byte identical, which
+/// saves most of the writes when re-uploading objects that rarely change. Returns whether an upload
+/// happened.
+pub async fn upload_object_if_changed<T: CannonicalS3ObjectLocation>(
+    s3_client: &Client,
+    addr: &T::AddressInfo,
+    object: &T,
+) -> anyhow::Result<bool> {
+    let key = get_openscrapers_json_key::<T>(addr);
+    let bucket = T::generate_bucket(addr);
+    S3Addr::new(s3_client, bucket, &key)
+        .upload_json_if_changed(&object)
+        .await
+}
+
 pub async fn delete_openscrapers_s3_object<T: CannonicalS3ObjectLocation>(
     s3_client: &Client,
     addr: &T::AddressInfo,
 ) -> anyhow::Result<()> {
     let key = get_openscrapers_json_key::<T>(addr);
-    let bucket = T::generate_bucket(&addr);
+    let bucket = T::generate_bucket(addr);
     S3Addr::new(s3_client, bucket, &key).delete_file().await
 }
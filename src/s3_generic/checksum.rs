@@ -0,0 +1,70 @@
+use std::fmt;
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// An expected checksum for an object body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    Crc32c(u32),
+    Sha256([u8; 32]),
+    Md5([u8; 16]),
+}
+
+impl Checksum {
+    /// Computes a checksum of the same algorithm as `self` over `bytes`.
+    pub fn compute_matching(&self, bytes: &[u8]) -> Self {
+        match self {
+            Self::Crc32c(_) => Self::Crc32c(crc32c::crc32c(bytes)),
+            Self::Sha256(_) => Self::Sha256(Sha256::digest(bytes).into()),
+            Self::Md5(_) => Self::Md5(Md5::digest(bytes).into()),
+        }
+    }
+
+    pub fn verify(&self, bytes: &[u8]) -> Result<(), ChecksumMismatch> {
+        let actual = self.compute_matching(bytes);
+        if actual == *self {
+            return Ok(());
+        }
+        Err(ChecksumMismatch {
+            expected: *self,
+            actual,
+        })
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Crc32c(val) => write!(f, "crc32c:{val:08x}"),
+            Self::Sha256(val) => write!(f, "sha256:{}", hex::encode(val)),
+            Self::Md5(val) => write!(f, "md5:{}", hex::encode(val)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Checksum mismatch, expected {expected} but got {actual}")]
+pub struct ChecksumMismatch {
+    pub expected: Checksum,
+    pub actual: Checksum,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_verification() {
+        let data = b"The quick brown fox jumped over the lazy dog";
+        let md5 = Checksum::Md5([0; 16]).compute_matching(data);
+        assert!(md5.verify(data).is_ok());
+        assert!(
+            md5.verify(b"the mitochondria is the powerhouse of a cell")
+                .is_err()
+        );
+        // Standard CRC32C check value
+        assert!(Checksum::Crc32c(0xe306_9283).verify(b"123456789").is_ok());
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{Read, Write};
+
+use anyhow::anyhow;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+
+pub const GZIP_CONTENT_ENCODING: &str = "gzip";
+pub const ZSTD_CONTENT_ENCODING: &str = "zstd";
+/// A good trade off for JSON, levels up to 9 shrink it further at a modest cost in speed.
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+pub fn gzip_compress(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+pub fn gzip_decompress(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+pub fn zstd_compress(bytes: &[u8], level: i32) -> anyhow::Result<Vec<u8>> {
+    Ok(zstd::encode_all(bytes, level)?)
+}
+
+pub fn zstd_decompress(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    Ok(zstd::decode_all(bytes)?)
+}
+
+/// Whether `bytes` start with the magic number of `content_encoding`, for objects that were
+/// compressed but stored without the header.
+pub fn has_magic_bytes(content_encoding: &str, bytes: &[u8]) -> bool {
+    match content_encoding {
+        GZIP_CONTENT_ENCODING => bytes.starts_with(&[0x1f, 0x8b]),
+        ZSTD_CONTENT_ENCODING => bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]),
+        _ => false,
+    }
+}
+
+/// Undoes whatever `Content-Encoding` the object was stored with, passing bytes through untouched
+/// when there is none.
+pub fn decode_content_encoding(
+    bytes: Vec<u8>,
+    content_encoding: Option<&str>,
+) -> anyhow::Result<Vec<u8>> {
+    match content_encoding.map(str::trim) {
+        None | Some("") | Some("identity") => Ok(bytes),
+        Some(GZIP_CONTENT_ENCODING) => gzip_decompress(&bytes),
+        Some(ZSTD_CONTENT_ENCODING) => zstd_decompress(&bytes),
+        Some(other) => Err(anyhow!("Unsupported content encoding: {other}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let original = br#"{"the mitochondria":"is the powerhouse of a cell"}"#.repeat(50);
+        let compressed = gzip_compress(&original).unwrap();
+        assert!(compressed.len() < original.len());
+        let decoded = decode_content_encoding(compressed, Some("gzip")).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_has_magic_bytes() {
+        let json = br#"{"key":"value"}"#;
+        assert!(has_magic_bytes("gzip", &gzip_compress(json).unwrap()));
+        assert!(has_magic_bytes(
+            "zstd",
+            &zstd_compress(json, DEFAULT_ZSTD_LEVEL).unwrap()
+        ));
+        assert!(!has_magic_bytes("gzip", json));
+        assert!(!has_magic_bytes("zstd", json));
+    }
+
+    #[test]
+    fn test_zstd_round_trip() {
+        let original = br#"{"the mitochondria":"is the powerhouse of a cell"}"#.repeat(50);
+        let compressed = zstd_compress(&original, DEFAULT_ZSTD_LEVEL).unwrap();
+        assert!(compressed.len() < original.len());
+        let decoded = decode_content_encoding(compressed, Some("zstd")).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_decode_without_encoding_is_passthrough() {
+        let original = b"plain bytes".to_vec();
+        let decoded = decode_content_encoding(original.clone(), None).unwrap();
+        assert_eq!(decoded, original);
+    }
+}
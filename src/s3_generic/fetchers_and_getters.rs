@@ -1,11 +1,36 @@
-use anyhow::anyhow;
+use anyhow::{Context, anyhow};
+use aws_sdk_s3::config::http::HttpResponse;
 use aws_sdk_s3::error::SdkError;
-use aws_sdk_s3::operation::get_object::GetObjectError;
-use aws_sdk_s3::types::ObjectCannedAcl;
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::operation::put_object::PutObjectError;
+use aws_sdk_s3::primitives::ByteStreamError;
+use aws_sdk_s3::types::{
+    BucketLocationConstraint, CompletedMultipartUpload, CompletedPart, CreateBucketConfiguration,
+    Delete, Grant, ObjectCannedAcl, ObjectIdentifier, Tag, Tagging,
+};
 use aws_sdk_s3::{Client as S3Client, primitives::ByteStream};
-use futures_util::{StreamExt, stream};
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use schemars::JsonSchema;
 use std::borrow::Cow;
-use tracing::{debug, error, info};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::{debug, error, info, warn};
+
+use crate::file_extension::mime_type_for_path;
+use crate::hash::{Algorithm, content_addressed_key, hash_bytes_hex};
+use crate::misc::retry::{RetryPolicy, retry_if};
+use crate::misc::truncate_str;
+use crate::s3_generic::S3Credentials;
+use crate::s3_generic::checksum::Checksum;
+use crate::s3_generic::compression::{
+    GZIP_CONTENT_ENCODING, ZSTD_CONTENT_ENCODING, decode_content_encoding, gzip_compress,
+    has_magic_bytes, zstd_compress,
+};
+use crate::s3_generic::upload_options::S3UploadOptions;
 
 // Conditional imports for rkyv
 #[cfg(feature = "rkyv")]
@@ -21,7 +46,98 @@ use rkyv::ser::allocator::ArenaHandle;
 #[cfg(feature = "rkyv")]
 use rkyv::util::AlignedVec;
 #[cfg(feature = "rkyv")]
-use rkyv::{Archive, Serialize};
+use rkyv::{Archive, Portable, Serialize};
+
+/// S3 rejects multipart parts smaller than this, except for the final part.
+pub const MIN_MULTIPART_PART_SIZE: usize = 5 * 1024 * 1024;
+pub const DEFAULT_MULTIPART_PART_SIZE: usize = 16 * 1024 * 1024;
+pub const DEFAULT_MULTIPART_CONCURRENCY: usize = 8;
+/// User metadata key [`S3Addr::upload_json_if_changed`] stores the body's hex SHA-256 under.
+pub const CONTENT_SHA256_METADATA_KEY: &str = "content-sha256";
+
+/// Splits a `ByteStream` into fixed size parts, only ever holding one part in memory at a time.
+struct PartReader {
+    body: ByteStream,
+    buffer: Vec<u8>,
+    part_size: usize,
+    finished: bool,
+}
+
+impl PartReader {
+    fn new(body: ByteStream, part_size: usize) -> Self {
+        PartReader {
+            body,
+            buffer: Vec::with_capacity(part_size),
+            part_size,
+            finished: false,
+        }
+    }
+
+    async fn next_part(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        while !self.finished && self.buffer.len() < self.part_size {
+            match self.body.next().await {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk?),
+                None => self.finished = true,
+            }
+        }
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+        if self.buffer.len() > self.part_size {
+            let rest = self.buffer.split_off(self.part_size);
+            return Ok(Some(std::mem::replace(&mut self.buffer, rest)));
+        }
+        Ok(Some(std::mem::take(&mut self.buffer)))
+    }
+}
+
+/// A client paired with the bucket it talks to, for services that only ever use one bucket.
+/// Addresses handed out by the same handle share a client, so copies between them stay server side.
+#[derive(Clone, Debug)]
+pub struct S3Bucket {
+    pub s3_client: S3Client,
+    pub bucket: String,
+}
+
+impl S3Bucket {
+    pub fn new(s3_client: S3Client, bucket: impl Into<String>) -> Self {
+        S3Bucket {
+            s3_client,
+            bucket: bucket.into(),
+        }
+    }
+
+    pub fn obj<'a>(&'a self, key: &'a str) -> S3Addr<'a> {
+        S3Addr::new(&self.s3_client, &self.bucket, key)
+    }
+
+    pub fn dir<'a>(&'a self, prefix: &'a str) -> S3DirectoryAddr<'a> {
+        S3DirectoryAddr::new(&self.s3_client, &self.bucket, prefix)
+    }
+
+    /// Deletes every object in the bucket, folder markers included, leaving the bucket itself in
+    /// place. The prefix deletes refuse to do this through an empty prefix.
+    pub async fn delete_all_objects(&self) -> anyhow::Result<()> {
+        warn!(%self.bucket, "Deleting every object in S3 bucket");
+        S3DirectoryAddr::new(&self.s3_client, &self.bucket, "")
+            .delete_prefix_unchecked()
+            .await
+    }
+
+    /// Stores `bytes` under a key derived from their SHA-256 hash (see
+    /// [`content_addressed_key`]) and returns the key. Identical content is only ever uploaded
+    /// once, later calls find the existing object and skip the upload.
+    pub async fn upload_content_addressed(&self, bytes: Vec<u8>) -> anyhow::Result<String> {
+        let key = content_addressed_key(Algorithm::Sha256, &bytes);
+        let addr = self.obj(&key);
+        if addr.exists().await? {
+            debug!(%self.bucket, %key, "Content addressed object already exists, skipping upload");
+        } else {
+            addr.upload_bytes(bytes).await?;
+        }
+        Ok(key)
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct S3Addr<'a> {
@@ -39,10 +155,29 @@ impl<'a> S3Addr<'a> {
         }
     }
 
+    /// The explicit content type from `opts`, falling back to one inferred from the key's extension.
+    fn content_type(&self, opts: &S3UploadOptions) -> Option<String> {
+        if let Some(content_type) = &opts.content_type {
+            return Some(content_type.clone());
+        }
+        mime_type_for_path(self.key).map(str::to_string)
+    }
+
     pub async fn download_json<T: serde::de::DeserializeOwned>(&self) -> anyhow::Result<T> {
         let bytes = self.download_bytes().await?;
-        let case = serde_json::from_slice(&bytes)?;
-        Ok(case)
+        self.parse_json(&bytes)
+    }
+
+    /// Parses a downloaded body, naming the object and quoting the start of the body on failure so
+    /// a malformed object can be found among thousands.
+    fn parse_json<T: serde::de::DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        serde_json::from_slice(bytes).with_context(|| {
+            let preview = String::from_utf8_lossy(&bytes[..bytes.len().min(200)]);
+            format!(
+                "Failed to parse JSON from s3://{}/{}, body starts with: {preview}",
+                self.bucket, self.key
+            )
+        })
     }
 
     pub async fn upload_json<T: serde::Serialize>(&self, obj: &T) -> anyhow::Result<()> {
@@ -51,7 +186,133 @@ impl<'a> S3Addr<'a> {
         // switch over to rkyv which should be way way faster than non pretty printed json.
         let obj_json_pretty_string = serde_json::to_string_pretty(obj)?;
         let obj_json_bytes = obj_json_pretty_string.into();
-        self.upload_bytes(obj_json_bytes).await
+        let opts = S3UploadOptions::default().with_content_type("application/json");
+        self.upload_bytes_with_opts(obj_json_bytes, &opts).await
+    }
+
+    /// Same as [`Self::upload_json`] but skips the upload when the stored object already has the
+    /// same bytes. Returns whether an upload happened. The body's SHA-256 is stored in the
+    /// [`CONTENT_SHA256_METADATA_KEY`] user metadata and compared first, which works for every
+    /// upload path. Objects written without it fall back to comparing the ETag with the body's MD5,
+    /// which only holds for plain unencrypted or SSE-S3 PUTs, so multipart, SSE-KMS and SSE-C
+    /// objects are re-uploaded once to pick up the hash.
+    pub async fn upload_json_if_changed<T: serde::Serialize>(
+        &self,
+        obj: &T,
+    ) -> anyhow::Result<bool> {
+        let obj_json_bytes: Vec<u8> = serde_json::to_string_pretty(obj)?.into();
+        let sha256 = hash_bytes_hex(Algorithm::Sha256, &obj_json_bytes);
+        if let Some(head) = self.head_if_exists().await? {
+            let stored_hash = head
+                .metadata()
+                .and_then(|metadata| metadata.get(CONTENT_SHA256_METADATA_KEY));
+            let unchanged = match stored_hash {
+                Some(stored_hash) => *stored_hash == sha256,
+                None => head.e_tag().is_some_and(|etag| {
+                    etag.trim_matches('"') == hash_bytes_hex(Algorithm::Md5, &obj_json_bytes)
+                }),
+            };
+            if unchanged {
+                debug!(%self.bucket, %self.key,"S3 object unchanged, skipping upload");
+                return Ok(false);
+            }
+        }
+        let opts = S3UploadOptions::default()
+            .with_content_type("application/json")
+            .with_metadata(HashMap::from([(
+                CONTENT_SHA256_METADATA_KEY.to_string(),
+                sha256,
+            )]));
+        self.upload_bytes_with_opts(obj_json_bytes, &opts).await?;
+        Ok(true)
+    }
+
+    /// The `head_object` output, or `None` when the object does not exist.
+    async fn head_if_exists(&self) -> anyhow::Result<Option<HeadObjectOutput>> {
+        let result = self
+            .s3_client
+            .head_object()
+            .bucket(self.bucket)
+            .key(self.key)
+            .send()
+            .await;
+        match result {
+            Ok(output) => Ok(Some(output)),
+            Err(err) if err.as_service_error().is_some_and(|err| err.is_not_found()) => Ok(None),
+            Err(err) => {
+                error!(%err,%self.bucket, %self.key,"Failed to head S3 object");
+                Err(anyhow!(err))
+            }
+        }
+    }
+
+    /// The object's ETag without the surrounding quotes, or `None` when it does not exist.
+    pub async fn etag(&self) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .head_if_exists()
+            .await?
+            .and_then(|output| output.e_tag.map(|etag| etag.trim_matches('"').to_string())))
+    }
+
+    /// Downloads JSON written by [`Self::upload_json_gzip`], only decompressing when the object has
+    /// a `Content-Encoding` or a gzip body, so plain [`Self::upload_json`] objects can be read as
+    /// well.
+    pub async fn download_json_gzip<T: serde::de::DeserializeOwned>(&self) -> anyhow::Result<T> {
+        self.download_json_decoded(GZIP_CONTENT_ENCODING).await
+    }
+
+    pub async fn upload_json_gzip<T: serde::Serialize>(&self, obj: &T) -> anyhow::Result<()> {
+        // No pretty printing here, the object is unreadable once compressed anyway.
+        let obj_json_bytes = serde_json::to_vec(obj)?;
+        let compressed = gzip_compress(&obj_json_bytes)?;
+        let opts = S3UploadOptions::default()
+            .with_content_type("application/json")
+            .with_content_encoding(GZIP_CONTENT_ENCODING);
+        self.upload_bytes_with_opts(compressed, &opts).await
+    }
+
+    /// Downloads JSON written by [`Self::upload_json_zstd`]. Like [`Self::download_json_gzip`] it
+    /// follows the object's `Content-Encoding`, so it reads any of the JSON upload formats.
+    pub async fn download_json_zstd<T: serde::de::DeserializeOwned>(&self) -> anyhow::Result<T> {
+        self.download_json_decoded(ZSTD_CONTENT_ENCODING).await
+    }
+
+    /// Follows the object's `Content-Encoding`, and decodes objects stored without one as
+    /// `encoding` when the body starts with its magic bytes (JSON never does).
+    async fn download_json_decoded<T: serde::de::DeserializeOwned>(
+        &self,
+        encoding: &str,
+    ) -> anyhow::Result<T> {
+        debug!(%self.bucket, %self.key, %encoding, "Downloading encoded json S3 object");
+        let output = self.get_object_raw().await?;
+        let content_encoding = output.content_encoding.clone();
+        let bytes = self.collect_body(output.body).await?;
+        let content_encoding = content_encoding
+            .as_deref()
+            .or_else(|| has_magic_bytes(encoding, &bytes).then_some(encoding));
+        let bytes = decode_content_encoding(bytes, content_encoding)?;
+        self.parse_json(&bytes)
+    }
+
+    pub async fn upload_json_zstd<T: serde::Serialize>(
+        &self,
+        obj: &T,
+        level: i32,
+    ) -> anyhow::Result<()> {
+        let obj_json_bytes = serde_json::to_vec(obj)?;
+        let compressed = zstd_compress(&obj_json_bytes, level)?;
+        let opts = S3UploadOptions::default()
+            .with_content_type("application/json")
+            .with_content_encoding(ZSTD_CONTENT_ENCODING);
+        self.upload_bytes_with_opts(compressed, &opts).await
+    }
+
+    /// Stores `bytes` zstd compressed with a `Content-Encoding: zstd` marker, read them back with
+    /// [`Self::download_bytes_decoded`].
+    pub async fn upload_bytes_zstd(&self, bytes: &[u8], level: i32) -> anyhow::Result<()> {
+        let compressed = zstd_compress(bytes, level)?;
+        let opts = S3UploadOptions::default().with_content_encoding(ZSTD_CONTENT_ENCODING);
+        self.upload_bytes_with_opts(compressed, &opts).await
     }
 
     #[cfg(feature = "rkyv")]
@@ -66,6 +327,21 @@ impl<'a> S3Addr<'a> {
         Ok(value)
     }
 
+    /// Downloads the raw archive straight into an aligned buffer without deserializing it, pair
+    /// with [`access_rkyv_archived`] to read fields in place.
+    #[cfg(feature = "rkyv")]
+    pub async fn download_rkyv_archived(&self) -> anyhow::Result<AlignedVec> {
+        debug!(%self.bucket, %self.key,"Downloading archived rkyv S3 object");
+        let output = self.get_object_raw().await?;
+        let capacity = output.content_length.unwrap_or_default().max(0) as usize;
+        let mut archived = AlignedVec::with_capacity(capacity);
+        let mut body = output.body;
+        while let Some(chunk) = body.next().await {
+            archived.extend_from_slice(&chunk?);
+        }
+        Ok(archived)
+    }
+
     #[cfg(feature = "rkyv")]
     pub async fn upload_rkyv<T>(&self, obj: &T) -> anyhow::Result<()>
     where
@@ -76,10 +352,10 @@ impl<'a> S3Addr<'a> {
         self.upload_bytes(bytes.to_vec()).await
     }
 
-    pub async fn download_bytes(&self) -> anyhow::Result<Vec<u8>> {
-        debug!(%self.bucket, %self.key,"Downloading S3 object");
-        let output = self
-            .s3_client
+    /// The SDK's `get_object` output with the body still uncollected, for callers that need headers
+    /// the convenience methods drop (cache control, storage class, SSE, etc).
+    pub async fn get_object_raw(&self) -> anyhow::Result<GetObjectOutput> {
+        self.s3_client
             .get_object()
             .bucket(self.bucket)
             .key(self.key)
@@ -96,22 +372,81 @@ impl<'a> S3Addr<'a> {
                         key = %self.key,
                         "S3 object not found (NoSuchKey)"
                     );
-                    return e; // still return the error, just not as high-level
+                    return anyhow!(e); // still return the error, just not as high-level
                 }
 
                 let err_dbg = format!("{:?}", e);
                 error!(
                     error = %e,
-                    error_debug = &err_dbg[..err_dbg.len().min(500)],
+                    error_debug = truncate_str(&err_dbg, 500),
                     bucket = %self.bucket,
                     key = %self.key,
                     "Failed to download S3 object"
                 );
-                e
+                anyhow!(e)
+            })
+    }
+
+    pub async fn download_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        debug!(%self.bucket, %self.key,"Downloading S3 object");
+        let output = self.get_object_raw().await?;
+
+        let bytes = self.collect_body(output.body).await?;
+
+        debug!(
+            %self.bucket,
+            %self.key,
+            bytes_len = %bytes.len(),
+            "Successfully downloaded file from s3"
+        );
+        Ok(bytes)
+    }
+
+    /// Downloads the object together with its metadata, saving the separate `head_object` request.
+    pub async fn download_bytes_with_meta(&self) -> anyhow::Result<(Vec<u8>, S3DownloadMeta)> {
+        debug!(%self.bucket, %self.key,"Downloading S3 object with metadata");
+        let output = self.get_object_raw().await?;
+        let meta = S3DownloadMeta {
+            content_length: output.content_length,
+            content_type: output.content_type.clone(),
+            etag: output.e_tag.clone(),
+            last_modified: output
+                .last_modified
+                .and_then(|time| DateTime::from_timestamp(time.secs(), time.subsec_nanos())),
+        };
+        let bytes = self.collect_body(output.body).await?;
+        Ok((bytes, meta))
+    }
+
+    /// Downloads a specific version of the object, for buckets with versioning enabled.
+    pub async fn download_bytes_version(&self, version_id: &str) -> anyhow::Result<Vec<u8>> {
+        debug!(%self.bucket, %self.key, %version_id, "Downloading S3 object version");
+        let output = self
+            .s3_client
+            .get_object()
+            .bucket(self.bucket)
+            .key(self.key)
+            .version_id(version_id)
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err,%self.bucket, %self.key, %version_id, "Failed to download S3 object version");
+                anyhow!(err)
             })?;
+        self.collect_body(output.body).await
+    }
 
-        let bytes = output
-            .body
+    /// Downloads the object and reverses its `Content-Encoding`, if any.
+    pub async fn download_bytes_decoded(&self) -> anyhow::Result<Vec<u8>> {
+        debug!(%self.bucket, %self.key,"Downloading S3 object");
+        let output = self.get_object_raw().await?;
+        let content_encoding = output.content_encoding.clone();
+        let bytes = self.collect_body(output.body).await?;
+        decode_content_encoding(bytes, content_encoding.as_deref())
+    }
+
+    async fn collect_body(&self, body: ByteStream) -> anyhow::Result<Vec<u8>> {
+        let bytes = body
             .collect()
             .await
             .map(|data| data.into_bytes().to_vec())
@@ -119,34 +454,608 @@ impl<'a> S3Addr<'a> {
                 error!(error = %e,%self.bucket, %self.key, "Failed to read response body");
                 e
             })?;
+        Ok(bytes)
+    }
+
+    /// Downloads the object only if its ETag no longer matches `etag`, returning `None` when S3
+    /// answers 304 Not Modified. Otherwise returns the bytes along with the current ETag.
+    pub async fn download_if_changed(
+        &self,
+        etag: Option<&str>,
+    ) -> anyhow::Result<Option<(Vec<u8>, String)>> {
+        debug!(%self.bucket, %self.key, ?etag, "Conditionally downloading S3 object");
+        let result = self
+            .s3_client
+            .get_object()
+            .bucket(self.bucket)
+            .key(self.key)
+            .set_if_none_match(etag.map(str::to_string))
+            .send()
+            .await;
+        let output = match result {
+            Ok(output) => output,
+            Err(err) if is_not_modified(&err) => {
+                debug!(%self.bucket, %self.key, "S3 object not modified");
+                return Ok(None);
+            }
+            Err(err) => {
+                error!(%err,%self.bucket, %self.key,"Failed to download S3 object");
+                return Err(anyhow!(err));
+            }
+        };
+        let new_etag = output.e_tag.clone().unwrap_or_default();
+        let bytes = self.collect_body(output.body).await?;
+        Ok(Some((bytes, new_etag)))
+    }
+
+    /// Downloads the object only if it was modified after `since`, returning `None` when S3 answers
+    /// 304 Not Modified. Any other error is still returned.
+    pub async fn download_if_modified_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        debug!(%self.bucket, %self.key, %since, "Conditionally downloading S3 object");
+        let result = self
+            .s3_client
+            .get_object()
+            .bucket(self.bucket)
+            .key(self.key)
+            .if_modified_since(aws_sdk_s3::primitives::DateTime::from_secs_and_nanos(
+                since.timestamp(),
+                since.timestamp_subsec_nanos(),
+            ))
+            .send()
+            .await;
+        let output = match result {
+            Ok(output) => output,
+            Err(err) if is_not_modified(&err) => {
+                debug!(%self.bucket, %self.key, "S3 object not modified");
+                return Ok(None);
+            }
+            Err(err) => {
+                error!(%err,%self.bucket, %self.key,"Failed to download S3 object");
+                return Err(anyhow!(err));
+            }
+        };
+        let bytes = self.collect_body(output.body).await?;
+        Ok(Some(bytes))
+    }
+
+    /// Downloads the object and checks the body against `expected`, failing with a
+    /// [`ChecksumMismatch`](crate::s3_generic::checksum::ChecksumMismatch) if it was corrupted.
+    pub async fn download_bytes_verified(&self, expected: Checksum) -> anyhow::Result<Vec<u8>> {
+        let bytes = self.download_bytes().await?;
+        expected.verify(&bytes).map_err(|err| {
+            error!(%err, %self.bucket, %self.key, "Downloaded S3 object failed checksum verification");
+            err
+        })?;
+        Ok(bytes)
+    }
+
+    /// Like [`Self::download_bytes`] but retries transient failures (5xx, throttling, timeouts,
+    /// dropped connections) according to `policy`. Missing objects and permission errors fail
+    /// immediately.
+    pub async fn download_bytes_retrying(&self, policy: &RetryPolicy) -> anyhow::Result<Vec<u8>> {
+        let is_retryable = |err: &anyhow::Error| {
+            let is_transient = is_transient_s3_error(err);
+            if is_transient {
+                warn!(%err, %self.bucket, %self.key, "Transient S3 download failure, retrying");
+            }
+            is_transient
+        };
+        retry_if(*policy, is_retryable, || self.download_bytes()).await
+    }
+
+    /// Streams the object chunk-by-chunk into `writer` instead of buffering it in memory, returns
+    /// the number of bytes written. If the stream fails partway through the returned error reports
+    /// how many bytes had already been written.
+    pub async fn download_to_writer<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> anyhow::Result<u64> {
+        debug!(%self.bucket, %self.key,"Streaming S3 object to writer");
+        let output = self.get_object_raw().await?;
+
+        let mut body = output.body;
+        let mut bytes_written: u64 = 0;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|err| {
+                error!(%err,%self.bucket, %self.key, %bytes_written, "Failed to read response body mid-stream");
+                anyhow!(err).context(format!(
+                    "S3 stream failed after writing {bytes_written} bytes"
+                ))
+            })?;
+            writer.write_all(&chunk).await.map_err(|err| {
+                error!(%err,%self.bucket, %self.key, %bytes_written, "Failed to write S3 chunk");
+                anyhow!(err).context(format!("Writer failed after writing {bytes_written} bytes"))
+            })?;
+            bytes_written += chunk.len() as u64;
+        }
+        writer.flush().await?;
 
         debug!(
             %self.bucket,
             %self.key,
-            bytes_len = %bytes.len(),
-            "Successfully downloaded file from s3"
+            %bytes_written,
+            "Successfully streamed file from s3"
         );
-        Ok(bytes)
+        Ok(bytes_written)
+    }
+
+    /// Whether the object exists, a missing object is `Ok(false)` rather than an error.
+    pub async fn exists(&self) -> anyhow::Result<bool> {
+        let result = self
+            .s3_client
+            .head_object()
+            .bucket(self.bucket)
+            .key(self.key)
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(err) if err.as_service_error().is_some_and(|err| err.is_not_found()) => Ok(false),
+            Err(err) => {
+                error!(%err,%self.bucket, %self.key,"Failed to check if S3 object exists");
+                Err(anyhow!(err))
+            }
+        }
     }
 
     pub async fn upload_bytes(&self, bytes: Vec<u8>) -> anyhow::Result<()> {
+        self.upload_bytes_with_opts(bytes, &S3UploadOptions::default())
+            .await
+    }
+
+    pub async fn upload_bytes_with_opts(
+        &self,
+        bytes: Vec<u8>,
+        opts: &S3UploadOptions,
+    ) -> anyhow::Result<()> {
         debug!(len=%bytes.len(), %self.bucket, %self.key,"Uploading bytes to S3 object");
+        self.put_object_with_opts(ByteStream::from(bytes), opts)
+            .await
+    }
+
+    /// Uploads `bytes` and reports whether an object was already there. With `allow_overwrite` the
+    /// upload is preceded by a `head_object`, so a concurrent writer can still slip in between.
+    /// Without it the upload is sent with `If-None-Match: *`, which S3 enforces atomically, and an
+    /// existing object is left untouched and reported as [`UploadOutcome::AlreadyExists`].
+    pub async fn upload_bytes_checked(
+        &self,
+        bytes: Vec<u8>,
+        allow_overwrite: bool,
+    ) -> anyhow::Result<UploadOutcome> {
+        if allow_overwrite {
+            let existed = self.exists().await?;
+            self.upload_bytes(bytes).await?;
+            return Ok(match existed {
+                true => UploadOutcome::Overwritten,
+                false => UploadOutcome::Created,
+            });
+        }
+
+        let opts = S3UploadOptions::default().with_only_if_absent(true);
+        match self.upload_bytes_with_opts(bytes, &opts).await {
+            Ok(()) => Ok(UploadOutcome::Created),
+            Err(err)
+                if err
+                    .downcast_ref::<SdkError<PutObjectError, HttpResponse>>()
+                    .is_some_and(is_precondition_failed) =>
+            {
+                Ok(UploadOutcome::AlreadyExists)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Like [`Self::upload_bytes`] but retries transient failures according to `policy`.
+    pub async fn upload_bytes_retrying(
+        &self,
+        bytes: Vec<u8>,
+        policy: &RetryPolicy,
+    ) -> anyhow::Result<()> {
+        let is_retryable = |err: &anyhow::Error| {
+            let is_transient = is_transient_s3_error(err);
+            if is_transient {
+                warn!(%err, %self.bucket, %self.key, "Transient S3 upload failure, retrying");
+            }
+            is_transient
+        };
+        retry_if(*policy, is_retryable, || self.upload_bytes(bytes.clone())).await
+    }
+
+    pub async fn upload_bytes_with_tags(
+        &self,
+        bytes: Vec<u8>,
+        tags: &[(String, String)],
+    ) -> anyhow::Result<()> {
+        let opts = S3UploadOptions::default().with_tags(tags);
+        self.upload_bytes_with_opts(bytes, &opts).await
+    }
+
+    /// Uploads `bytes` with user metadata attached, read it back with [`Self::get_metadata`].
+    pub async fn upload_bytes_with_metadata(
+        &self,
+        bytes: Vec<u8>,
+        metadata: HashMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let opts = S3UploadOptions::default().with_metadata(metadata);
+        self.upload_bytes_with_opts(bytes, &opts).await
+    }
+
+    /// Reads the object's user metadata with a `head_object`, keys come back without the
+    /// `x-amz-meta-` prefix.
+    pub async fn get_metadata(&self) -> anyhow::Result<HashMap<String, String>> {
+        let output = self
+            .s3_client
+            .head_object()
+            .bucket(self.bucket)
+            .key(self.key)
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err,%self.bucket, %self.key,"Failed to get S3 object metadata");
+                anyhow!(err)
+            })?;
+        Ok(output.metadata.unwrap_or_default())
+    }
+
+    pub async fn get_tags(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let output = self
+            .s3_client
+            .get_object_tagging()
+            .bucket(self.bucket)
+            .key(self.key)
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err,%self.bucket, %self.key,"Failed to get S3 object tags");
+                anyhow!(err)
+            })?;
+        Ok(output
+            .tag_set
+            .into_iter()
+            .map(|tag| (tag.key, tag.value))
+            .collect())
+    }
+
+    /// Replaces the object's ACL with a canned one, e.g. [`ObjectCannedAcl::Private`] to
+    /// un-publish an object that every upload makes public-read, without re-uploading the body.
+    pub async fn set_acl(&self, acl: ObjectCannedAcl) -> anyhow::Result<()> {
+        self.s3_client
+            .put_object_acl()
+            .bucket(self.bucket)
+            .key(self.key)
+            .acl(acl)
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err,%self.bucket, %self.key,"Failed to set S3 object ACL");
+                anyhow!(err)
+            })?;
+        Ok(())
+    }
+
+    /// The grants currently making up the object's ACL.
+    pub async fn get_acl(&self) -> anyhow::Result<Vec<Grant>> {
+        let output = self
+            .s3_client
+            .get_object_acl()
+            .bucket(self.bucket)
+            .key(self.key)
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err,%self.bucket, %self.key,"Failed to get S3 object ACL");
+                anyhow!(err)
+            })?;
+        Ok(output.grants.unwrap_or_default())
+    }
+
+    /// Replaces the full tag set on the object.
+    pub async fn put_tags(&self, tags: &[(String, String)]) -> anyhow::Result<()> {
+        let tag_set = tags
+            .iter()
+            .map(|(key, value)| Tag::builder().key(key).value(value).build())
+            .collect::<Result<Vec<_>, _>>()?;
+        let tagging = Tagging::builder().set_tag_set(Some(tag_set)).build()?;
+        self.s3_client
+            .put_object_tagging()
+            .bucket(self.bucket)
+            .key(self.key)
+            .tagging(tagging)
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err,%self.bucket, %self.key,"Failed to put S3 object tags");
+                anyhow!(err)
+            })?;
+        debug!(%self.bucket, %self.key, tag_count = %tags.len(), "Successfully tagged s3 object");
+        Ok(())
+    }
+
+    /// Uploads a `ByteStream` body without collecting it into memory first.
+    pub async fn upload_from_stream(&self, body: ByteStream) -> anyhow::Result<()> {
+        self.upload_from_stream_with_opts(body, &S3UploadOptions::default())
+            .await
+    }
+
+    pub async fn upload_from_stream_with_opts(
+        &self,
+        body: ByteStream,
+        opts: &S3UploadOptions,
+    ) -> anyhow::Result<()> {
+        debug!(%self.bucket, %self.key,"Streaming upload to S3 object");
+        self.put_object_with_opts(body, opts).await
+    }
+
+    async fn put_object_with_opts(
+        &self,
+        body: ByteStream,
+        opts: &S3UploadOptions,
+    ) -> anyhow::Result<()> {
         self.s3_client
             .put_object()
             .bucket(self.bucket)
             .key(self.key)
-            .body(ByteStream::from(bytes))
+            .body(body)
             .acl(ObjectCannedAcl::PublicRead) // 👈 make object public
+            .set_server_side_encryption(opts.server_side_encryption())
+            .set_ssekms_key_id(opts.kms_key_id())
+            .set_content_type(self.content_type(opts))
+            .set_tagging(opts.tagging())
+            .set_content_encoding(opts.content_encoding.clone())
+            .set_metadata(opts.user_metadata())
+            .set_if_none_match(opts.only_if_absent.then(|| "*".to_string()))
             .send()
             .await
             .map_err(|err| {
-                error!(%err,%self.bucket, %self.key,"Failed to upload S3 object");
+                if is_precondition_failed(&err) {
+                    debug!(%self.bucket, %self.key,"S3 object already exists, not overwriting");
+                } else {
+                    error!(%err,%self.bucket, %self.key,"Failed to upload S3 object");
+                }
                 anyhow!(err)
             })?;
         debug!( %self.bucket, %self.key,"Successfully uploaded s3 object");
         Ok(())
     }
 
+    /// Streams a file from disk into the object, so memory use stays bounded regardless of file size.
+    /// Files larger than a single part are sent as a multipart upload.
+    pub async fn upload_from_path(&self, path: &Path) -> anyhow::Result<()> {
+        let body = ByteStream::from_path(path).await.map_err(|err| {
+            error!(%err, path = %path.display(), "Failed to open file for S3 upload");
+            anyhow!(err)
+        })?;
+        self.upload_multipart(body, DEFAULT_MULTIPART_PART_SIZE)
+            .await
+    }
+
+    pub async fn upload_multipart(&self, body: ByteStream, part_size: usize) -> anyhow::Result<()> {
+        self.upload_multipart_with_concurrency(body, part_size, DEFAULT_MULTIPART_CONCURRENCY)
+            .await
+    }
+
+    pub async fn upload_multipart_with_concurrency(
+        &self,
+        body: ByteStream,
+        part_size: usize,
+        concurrency: usize,
+    ) -> anyhow::Result<()> {
+        self.upload_multipart_with_opts(body, part_size, concurrency, &S3UploadOptions::default())
+            .await
+    }
+
+    /// Uploads `body` in parts of `part_size` bytes, with up to `concurrency` parts in flight. Bodies
+    /// that fit in a single part fall back to a normal PUT. On any failure the multipart upload is
+    /// aborted so no orphaned parts are left behind.
+    pub async fn upload_multipart_with_opts(
+        &self,
+        body: ByteStream,
+        part_size: usize,
+        concurrency: usize,
+        opts: &S3UploadOptions,
+    ) -> anyhow::Result<()> {
+        if part_size < MIN_MULTIPART_PART_SIZE {
+            return Err(anyhow!(
+                "Multipart part size {part_size} is below the S3 minimum of {MIN_MULTIPART_PART_SIZE}"
+            ));
+        }
+        let mut reader = PartReader::new(body, part_size);
+        let first_part = reader.next_part().await?.unwrap_or_default();
+        if reader.finished && reader.buffer.is_empty() {
+            return self.upload_bytes_with_opts(first_part, opts).await;
+        }
+
+        let upload_id = self
+            .s3_client
+            .create_multipart_upload()
+            .bucket(self.bucket)
+            .key(self.key)
+            .acl(ObjectCannedAcl::PublicRead)
+            .set_server_side_encryption(opts.server_side_encryption())
+            .set_ssekms_key_id(opts.kms_key_id())
+            .set_content_type(self.content_type(opts))
+            .set_tagging(opts.tagging())
+            .set_content_encoding(opts.content_encoding.clone())
+            .set_metadata(opts.user_metadata())
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err,%self.bucket, %self.key,"Failed to create multipart upload");
+                anyhow!(err)
+            })?
+            .upload_id
+            .ok_or_else(|| anyhow!("S3 did not return an upload id for multipart upload"))?;
+        debug!(%self.bucket, %self.key, %upload_id, %part_size, "Started multipart upload");
+
+        match self
+            .upload_parts(&upload_id, first_part, reader, concurrency)
+            .await
+        {
+            Ok(completed_parts) => {
+                let part_count = completed_parts.len();
+                self.s3_client
+                    .complete_multipart_upload()
+                    .bucket(self.bucket)
+                    .key(self.key)
+                    .upload_id(&upload_id)
+                    .multipart_upload(
+                        CompletedMultipartUpload::builder()
+                            .set_parts(Some(completed_parts))
+                            .build(),
+                    )
+                    .set_if_none_match(opts.only_if_absent.then(|| "*".to_string()))
+                    .send()
+                    .await
+                    .map_err(|err| {
+                        error!(%err,%self.bucket, %self.key, %upload_id, "Failed to complete multipart upload");
+                        anyhow!(err)
+                    })?;
+                debug!(%self.bucket, %self.key, %part_count, "Successfully completed multipart upload");
+                Ok(())
+            }
+            Err(err) => {
+                error!(%err,%self.bucket, %self.key, %upload_id, "Multipart upload failed, aborting");
+                if let Err(abort_err) = self
+                    .s3_client
+                    .abort_multipart_upload()
+                    .bucket(self.bucket)
+                    .key(self.key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await
+                {
+                    error!(err = %abort_err,%self.bucket, %self.key, %upload_id, "Failed to abort multipart upload");
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn upload_parts(
+        &self,
+        upload_id: &str,
+        first_part: Vec<u8>,
+        reader: PartReader,
+        concurrency: usize,
+    ) -> anyhow::Result<Vec<CompletedPart>> {
+        let remaining_parts =
+            stream::try_unfold((reader, 2), |(mut reader, part_number)| async move {
+                Ok::<_, anyhow::Error>(
+                    reader
+                        .next_part()
+                        .await?
+                        .map(|part| ((part_number, part), (reader, part_number + 1))),
+                )
+            });
+        let mut completed_parts: Vec<CompletedPart> = stream::once(async { Ok((1, first_part)) })
+            .chain(remaining_parts)
+            .map_ok(|(part_number, part): (i32, Vec<u8>)| async move {
+                let part_len = part.len();
+                let output = self
+                    .s3_client
+                    .upload_part()
+                    .bucket(self.bucket)
+                    .key(self.key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .body(ByteStream::from(part))
+                    .send()
+                    .await
+                    .map_err(|err| {
+                        error!(%err,%self.bucket, %self.key, %part_number, "Failed to upload part");
+                        anyhow!(err)
+                    })?;
+                debug!(%self.bucket, %self.key, %part_number, %part_len, "Uploaded part");
+                Ok(CompletedPart::builder()
+                    .set_e_tag(output.e_tag)
+                    .part_number(part_number)
+                    .build())
+            })
+            .try_buffer_unordered(concurrency.max(1))
+            .try_collect()
+            .await?;
+        completed_parts.sort_by_key(|part| part.part_number);
+        Ok(completed_parts)
+    }
+
+    /// Copies this object to `destination` by streaming it through this process rather than using
+    /// a server side copy, which lets the two addresses live behind entirely different clients.
+    /// The content type, content encoding, user metadata and tags are carried over so the copy
+    /// reads back the same way as the original.
+    pub async fn stream_copy_to(&self, destination: &S3Addr<'_>) -> anyhow::Result<()> {
+        debug!(src_bucket = %self.bucket, src_key = %self.key, dest_bucket = %destination.bucket, dest_key = %destination.key, "Streaming S3 object between clients");
+        let output = self.get_object_raw().await?;
+        let tags = if output.tag_count.unwrap_or_default() > 0 {
+            self.get_tags().await?
+        } else {
+            Vec::new()
+        };
+        let opts = S3UploadOptions {
+            content_type: output.content_type,
+            content_encoding: output.content_encoding,
+            metadata: output.metadata.unwrap_or_default(),
+            tags,
+            ..S3UploadOptions::default()
+        };
+        destination
+            .upload_multipart_with_opts(
+                output.body,
+                DEFAULT_MULTIPART_PART_SIZE,
+                DEFAULT_MULTIPART_CONCURRENCY,
+                &opts,
+            )
+            .await
+    }
+
+    async fn server_side_copy_to(&self, destination: &S3Addr<'_>) -> anyhow::Result<()> {
+        debug!(src_bucket = %self.bucket, src_key = %self.key, dest_bucket = %destination.bucket, dest_key = %destination.key, "Copying S3 object");
+        destination
+            .s3_client
+            .copy_object()
+            .bucket(destination.bucket)
+            .key(destination.key)
+            .copy_source(encode_copy_source(self.bucket, self.key))
+            .acl(ObjectCannedAcl::PublicRead) // match the ACL every upload gets
+            .send()
+            .await
+            .map_err(|err| {
+                error!(%err, src_bucket = %self.bucket, src_key = %self.key, dest_bucket = %destination.bucket, dest_key = %destination.key, "Failed to copy S3 object");
+                anyhow!(err)
+            })?;
+        Ok(())
+    }
+
+    /// Copies this object to `destination` with a server-side `copy_object`, use
+    /// [`Self::copy_to_with_mode`] with [`CopyMode::Stream`] when the destination client can't read
+    /// the source.
+    pub async fn copy_to(&self, destination: &S3Addr<'_>) -> anyhow::Result<()> {
+        self.copy_to_with_mode(destination, CopyMode::default())
+            .await
+    }
+
+    pub async fn copy_to_with_mode(
+        &self,
+        destination: &S3Addr<'_>,
+        mode: CopyMode,
+    ) -> anyhow::Result<()> {
+        match mode {
+            CopyMode::ServerSide => self.server_side_copy_to(destination).await,
+            CopyMode::Stream => self.stream_copy_to(destination).await,
+        }
+    }
+
+    /// Moves this object to `destination`, in the same or a different bucket. The source is only
+    /// deleted once the copy has succeeded.
+    pub async fn move_to(&self, destination: &S3Addr<'_>) -> anyhow::Result<()> {
+        self.copy_to(destination).await?;
+        self.delete_file().await?;
+        info!(src_bucket = %self.bucket, src_key = %self.key, dest_bucket = %destination.bucket, dest_key = %destination.key, "Successfully moved s3 object");
+        Ok(())
+    }
+
     pub async fn delete_file(&self) -> anyhow::Result<()> {
         debug!( %self.bucket, %self.key,"Deleting file from S3");
         self.s3_client
@@ -164,16 +1073,242 @@ impl<'a> S3Addr<'a> {
     }
 }
 
+/// Validates the bytes from [`S3Addr::download_rkyv_archived`] and returns the archived view of `T`,
+/// without allocating a full `T`.
+#[cfg(feature = "rkyv")]
+pub fn access_rkyv_archived<T>(bytes: &AlignedVec) -> anyhow::Result<&T::Archived>
+where
+    T: Archive,
+    T::Archived: Portable + for<'b> CheckBytes<HighValidator<'b, rkyv::rancor::Error>>,
+{
+    let archived = rkyv::access::<T::Archived, rkyv::rancor::Error>(bytes)?;
+    Ok(archived)
+}
+
+fn is_transient_sdk_error<E>(err: &SdkError<E, HttpResponse>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            true
+        }
+        SdkError::ServiceError(service_err) => {
+            let status = service_err.raw().status();
+            status.is_server_error() || status.as_u16() == 429
+        }
+        _ => false,
+    }
+}
+
+/// Whether an error from one of the `S3Addr` methods is worth retrying. Anything that isn't a
+/// recognisably transient S3 failure, including 403s and 404s, is treated as permanent.
+pub fn is_transient_s3_error(err: &anyhow::Error) -> bool {
+    if let Some(sdk_err) = err.downcast_ref::<SdkError<GetObjectError, HttpResponse>>() {
+        return is_transient_sdk_error(sdk_err);
+    }
+    if let Some(sdk_err) = err.downcast_ref::<SdkError<PutObjectError, HttpResponse>>() {
+        return is_transient_sdk_error(sdk_err);
+    }
+    // The connection dropping while the body is being read
+    err.downcast_ref::<ByteStreamError>().is_some()
+}
+
+fn is_not_modified<E>(err: &SdkError<E, HttpResponse>) -> bool {
+    err.raw_response()
+        .is_some_and(|response| response.status().as_u16() == 304)
+}
+
+/// Makes sure `bucket` exists, creating it when `head_bucket` reports it missing. Any other error
+/// from `head_bucket` (no access, bad credentials) is returned rather than papered over.
+pub async fn ensure_bucket(s3_client: &S3Client, bucket: &str) -> anyhow::Result<()> {
+    let err = match s3_client.head_bucket().bucket(bucket).send().await {
+        Ok(_) => return Ok(()),
+        Err(err) => err,
+    };
+    if !err.as_service_error().is_some_and(|err| err.is_not_found()) {
+        error!(%err, %bucket, "Failed to check if S3 bucket exists");
+        return Err(anyhow!(err));
+    }
+
+    info!(%bucket, "S3 bucket does not exist, creating it");
+    let mut request = s3_client.create_bucket().bucket(bucket);
+    // us-east-1 is the implicit default and S3 rejects it as an explicit location constraint.
+    if let Some(region) = s3_client.config().region()
+        && region.as_ref() != "us-east-1"
+    {
+        request = request.create_bucket_configuration(
+            CreateBucketConfiguration::builder()
+                .location_constraint(BucketLocationConstraint::from(region.as_ref()))
+                .build(),
+        );
+    }
+    request.send().await.map_err(|err| {
+        error!(%err, %bucket, "Failed to create S3 bucket");
+        anyhow!(err)
+    })?;
+    Ok(())
+}
+
+/// Builds the `x-amz-copy-source` value for `copy_object`. The key has to be URL-encoded, but the
+/// `/` separators are kept so S3 still reads it as the same path.
+fn encode_copy_source(bucket: &str, key: &str) -> String {
+    let encoded_key = key
+        .split('/')
+        .map(|segment| urlencoding::encode(segment))
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{bucket}/{encoded_key}")
+}
+
+/// The status S3 answers with when an `If-None-Match: *` upload finds the key already taken.
+fn is_precondition_failed<E>(err: &SdkError<E, HttpResponse>) -> bool {
+    err.raw_response()
+        .is_some_and(|response| response.status().as_u16() == 412)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadOutcome {
+    /// Nothing existed under the key before.
+    Created,
+    /// An existing object was replaced.
+    Overwritten,
+    /// An object already existed and was left as is.
+    AlreadyExists,
+}
+
+/// S3 accepts at most this many keys in a single `DeleteObjects` request.
+pub(crate) const MAX_DELETE_BATCH_SIZE: usize = 1000;
+pub const DEFAULT_COPY_CONCURRENCY: usize = 25;
+pub const DEFAULT_LIST_CONCURRENCY: usize = 16;
+
+/// How objects get copied between two addresses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CopyMode {
+    /// A server side `copy_object`, the destination client must be able to read the source.
+    #[default]
+    ServerSide,
+    /// Downloads and re-uploads through this process, for copies between accounts or providers.
+    Stream,
+}
+
+impl CopyMode {
+    /// Server side when both sets of credentials point at the same account (see
+    /// [`S3Credentials::same_account`]), streamed otherwise.
+    pub fn for_credentials(source: &S3Credentials, destination: &S3Credentials) -> Self {
+        if source.same_account(destination) {
+            CopyMode::ServerSide
+        } else {
+            CopyMode::Stream
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, JsonSchema,
+)]
+pub struct CopyReport {
+    pub total: usize,
+    pub succeeded: usize,
+    /// Pairs of (source key, error message) for every object that could not be copied.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Returned by the prefix deletes when the prefix is empty, which would mean the whole bucket. Use
+/// [`S3Bucket::delete_all_objects`] when that really is the intent.
+#[derive(Debug, Error)]
+#[error("Refusing to delete every object in bucket {bucket} through an empty prefix")]
+pub struct EmptyPrefixDeleteError {
+    pub bucket: String,
+}
+
+/// Returned by [`S3DirectoryAddr::copy_into`] when some objects failed to copy, downcast the
+/// `anyhow::Error` to get at the report.
+#[derive(Debug, Error)]
+#[error("Failed to copy {} of {} S3 objects", report.failed.len(), report.total)]
+pub struct CopyError {
+    pub report: CopyReport,
+}
+
+#[derive(Debug, Error)]
+#[error("Failed to delete {} S3 objects", failed.len())]
+pub struct BatchDeleteError {
+    /// Pairs of (key, error message) for every object S3 refused to delete.
+    pub failed: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, JsonSchema)]
+pub struct S3ObjectMeta {
+    pub key: String,
+    pub size: i64,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub etag: Option<String>,
+}
+
+impl From<&aws_sdk_s3::types::Object> for S3ObjectMeta {
+    fn from(object: &aws_sdk_s3::types::Object) -> Self {
+        S3ObjectMeta {
+            key: object.key().unwrap_or_default().to_string(),
+            size: object.size().unwrap_or_default(),
+            last_modified: object
+                .last_modified()
+                .and_then(|time| DateTime::from_timestamp(time.secs(), time.subsec_nanos())),
+            etag: object.e_tag().map(str::to_string),
+        }
+    }
+}
+
+/// Metadata returned alongside the body by [`S3Addr::download_bytes_with_meta`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, JsonSchema)]
+pub struct S3DownloadMeta {
+    pub content_length: Option<i64>,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// One entry from [`S3DirectoryAddr::list_versions`]. Delete markers are included so an accidental
+/// delete can be told apart from an overwrite.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, JsonSchema)]
+pub struct S3ObjectVersion {
+    pub key: String,
+    pub version_id: String,
+    pub is_latest: bool,
+    pub is_delete_marker: bool,
+    pub size: i64,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub etag: Option<String>,
+}
+
+/// Keys returned from a listing, together with the client and bucket they came from so each one
+/// can be turned straight into an [`S3Addr`].
+#[derive(Clone)]
+pub struct S3KeyListing<'a> {
+    pub s3_client: &'a S3Client,
+    pub bucket: &'a str,
+    pub keys: Vec<String>,
+}
+
+impl S3KeyListing<'_> {
+    pub fn addrs(&self) -> impl Iterator<Item = S3Addr<'_>> {
+        self.keys
+            .iter()
+            .map(|key| S3Addr::new(self.s3_client, self.bucket, key))
+    }
+}
+
 #[derive(Clone)]
 pub struct S3DirectoryAddr<'a> {
     pub s3_client: &'a S3Client,
     pub bucket: &'a str,
     pub prefix: Cow<'a, str>,
+    /// Leave out the zero-byte `.../` objects some tools create to represent folders when listing.
+    /// On by default.
+    pub skip_directory_markers: bool,
 }
 
 impl<'a> S3DirectoryAddr<'a> {
+    /// Normalizes `prefix` to end in `/`, except for an empty prefix which stays empty and covers
+    /// the whole bucket.
     pub fn new(s3_client: &'a S3Client, bucket: &'a str, prefix: &'a str) -> Self {
-        let actual_prefix = if prefix.ends_with('/') {
+        let actual_prefix = if prefix.is_empty() || prefix.ends_with('/') {
             Cow::Borrowed(prefix)
         } else {
             Cow::Owned(format!("{}/", prefix))
@@ -182,41 +1317,345 @@ impl<'a> S3DirectoryAddr<'a> {
             s3_client,
             bucket,
             prefix: actual_prefix,
+            skip_directory_markers: true,
         }
     }
 
+    pub fn with_skip_directory_markers(mut self, skip_directory_markers: bool) -> Self {
+        self.skip_directory_markers = skip_directory_markers;
+        self
+    }
+
+    /// Whether a listed key should be returned, folder markers are keys ending in `/`.
+    fn is_listed(&self, key: &str) -> bool {
+        !(self.skip_directory_markers && key.ends_with('/'))
+    }
+
+    /// Deletes every object under the prefix, in batches of up to 1000 keys per request. Keys that
+    /// S3 refuses to delete are collected and returned as a [`BatchDeleteError`]. An empty prefix
+    /// fails with [`EmptyPrefixDeleteError`] rather than emptying the bucket.
     pub async fn delete_all(&self) -> anyhow::Result<()> {
-        let mut continuation_token: Option<String> = None;
+        self.ensure_prefix_for_delete()?;
+        self.delete_prefix_unchecked().await
+    }
+
+    /// Guards the prefix deletes against an empty prefix, which lists the whole bucket.
+    pub(crate) fn ensure_prefix_for_delete(&self) -> Result<(), EmptyPrefixDeleteError> {
+        if self.prefix.is_empty() {
+            error!(%self.bucket, "Refusing to delete with an empty prefix");
+            return Err(EmptyPrefixDeleteError {
+                bucket: self.bucket.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn delete_prefix_unchecked(&self) -> anyhow::Result<()> {
+        let mut failed = Vec::new();
+        let mut deleted_count: usize = 0;
+
+        let mut stream = self
+            .s3_client
+            .list_objects_v2()
+            .bucket(self.bucket)
+            .prefix(&*self.prefix)
+            .max_keys(MAX_DELETE_BATCH_SIZE as i32)
+            .into_paginator()
+            .send();
+
+        while let Some(result) = stream.next().await {
+            let keys: Vec<String> = result?
+                .contents
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|object| object.key)
+                .collect();
+            let batch_failures = self.delete_keys(&keys).await?;
+            deleted_count += keys.len() - batch_failures.len();
+            failed.extend(batch_failures);
+        }
+
+        if !failed.is_empty() {
+            error!(
+                %self.bucket,
+                prefix = %self.prefix,
+                failed_count = %failed.len(),
+                %deleted_count,
+                "Some s3 objects could not be deleted"
+            );
+            return Err(BatchDeleteError { failed }.into());
+        }
+        debug!(%self.bucket, prefix = %self.prefix, %deleted_count, "Successfully deleted s3 prefix");
+        Ok(())
+    }
+
+    /// Returns every key [`Self::delete_all`] would delete, folder markers included, without
+    /// deleting anything.
+    pub async fn delete_all_dry_run(&self) -> anyhow::Result<Vec<String>> {
+        self.clone()
+            .with_skip_directory_markers(false)
+            .list_all()
+            .await
+    }
+
+    /// Like [`Self::delete_all`] but only deletes the keys [`Self::list_all_filtered`] returns for
+    /// `predicate`, folder markers included. Returns how many objects were deleted, and refuses an
+    /// empty prefix the same way.
+    pub async fn delete_all_filtered(
+        &self,
+        predicate: impl Fn(&str) -> bool,
+    ) -> anyhow::Result<usize> {
+        self.ensure_prefix_for_delete()?;
+        let keys = self
+            .clone()
+            .with_skip_directory_markers(false)
+            .list_all_filtered(predicate)
+            .await?;
+        let failed = self.delete_keys(&keys).await?;
+        let deleted_count = keys.len() - failed.len();
+        if !failed.is_empty() {
+            error!(
+                %self.bucket,
+                prefix = %self.prefix,
+                failed_count = %failed.len(),
+                %deleted_count,
+                "Some s3 objects could not be deleted"
+            );
+            return Err(BatchDeleteError { failed }.into());
+        }
+        debug!(%self.bucket, prefix = %self.prefix, %deleted_count, "Successfully deleted matching s3 objects");
+        Ok(deleted_count)
+    }
+
+    /// Deletes `keys` from the bucket in batches, returning (key, error message) for every object
+    /// S3 refused to delete.
+    pub(crate) async fn delete_keys(
+        &self,
+        keys: &[String],
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let mut failed = Vec::new();
+        for batch in keys.chunks(MAX_DELETE_BATCH_SIZE) {
+            let identifiers = batch
+                .iter()
+                .map(|key| ObjectIdentifier::builder().key(key).build())
+                .collect::<Result<Vec<_>, _>>()?;
+            let delete = Delete::builder()
+                .set_objects(Some(identifiers))
+                .quiet(true)
+                .build()?;
+            let response = self
+                .s3_client
+                .delete_objects()
+                .bucket(self.bucket)
+                .delete(delete)
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(%err,%self.bucket, prefix = %self.prefix, "Failed to batch delete s3 objects");
+                    anyhow!(err)
+                })?;
+            failed.extend(response.errors.unwrap_or_default().into_iter().map(|err| {
+                (
+                    err.key.unwrap_or_default(),
+                    err.message.or(err.code).unwrap_or_default(),
+                )
+            }));
+        }
+        Ok(failed)
+    }
+
+    /// Aborts every in-progress multipart upload under the prefix, so interrupted uploads stop
+    /// accruing storage charges for their orphaned parts. Returns how many uploads were aborted.
+    pub async fn abort_incomplete_uploads(&self) -> anyhow::Result<usize> {
+        let mut aborted_count: usize = 0;
+        let mut failed_count: usize = 0;
+        let mut key_marker = None;
+        let mut upload_id_marker = None;
 
         loop {
-            let mut list_request = self
+            let page = self
                 .s3_client
-                .list_objects_v2()
+                .list_multipart_uploads()
                 .bucket(self.bucket)
-                .prefix(&*self.prefix);
-            if let Some(token) = continuation_token {
-                list_request = list_request.continuation_token(token);
-            }
-            let response = list_request.send().await?;
-            if let Some(objects) = response.contents {
-                for object in objects {
-                    if let Some(key) = object.key {
-                        S3Addr::new(self.s3_client, self.bucket, &key)
-                            .delete_file()
-                            .await?;
+                .prefix(&*self.prefix)
+                .set_key_marker(key_marker.take())
+                .set_upload_id_marker(upload_id_marker.take())
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(%err,%self.bucket, prefix = %self.prefix, "Failed to list multipart uploads");
+                    anyhow!(err)
+                })?;
+
+            for upload in page.uploads() {
+                let (Some(key), Some(upload_id)) = (upload.key(), upload.upload_id()) else {
+                    continue;
+                };
+                let result = self
+                    .s3_client
+                    .abort_multipart_upload()
+                    .bucket(self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .send()
+                    .await;
+                match result {
+                    Ok(_) => aborted_count += 1,
+                    Err(err) => {
+                        error!(%err,%self.bucket, %key, %upload_id, "Failed to abort multipart upload");
+                        failed_count += 1;
                     }
                 }
             }
-            match response.is_truncated {
-                Some(true) => continuation_token = response.next_continuation_token,
-                _ => break,
+
+            if !page.is_truncated().unwrap_or(false) {
+                break;
             }
+            key_marker = page.next_key_marker().map(str::to_string);
+            upload_id_marker = page.next_upload_id_marker().map(str::to_string);
         }
-        Ok(())
+
+        if failed_count > 0 {
+            return Err(anyhow!(
+                "Failed to abort {failed_count} of {} multipart uploads",
+                failed_count + aborted_count
+            ));
+        }
+        debug!(%self.bucket, prefix = %self.prefix, %aborted_count, "Aborted incomplete multipart uploads");
+        Ok(aborted_count)
     }
 
     pub async fn list_all(&self) -> anyhow::Result<Vec<String>> {
-        let mut prefix_names = Vec::new();
+        self.list_stream().try_collect().await
+    }
+
+    /// Yields the keys under the prefix as each page of the listing arrives, so processing can
+    /// start straight away and memory stays bounded to a single page.
+    pub fn list_stream(&self) -> impl Stream<Item = anyhow::Result<String>> + 'a {
+        let pages = self
+            .s3_client
+            .list_objects_v2()
+            .bucket(self.bucket)
+            .prefix(self.prefix.clone().into_owned())
+            .into_paginator()
+            .send();
+        let skip_directory_markers = self.skip_directory_markers;
+        stream::unfold(pages, move |mut pages| async move {
+            let page = pages.next().await?;
+            let keys = page.map_err(anyhow::Error::from).map(|page| {
+                page.contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|object| object.key)
+                    .filter(|key| !(skip_directory_markers && key.ends_with('/')))
+                    .map(Ok)
+                    .collect::<Vec<_>>()
+            });
+            Some((keys, pages))
+        })
+        .map_ok(stream::iter)
+        .try_flatten()
+    }
+
+    /// Lists the prefix by first finding its immediate subprefixes with a `/` delimiter and then
+    /// listing each of them concurrently, which is much faster for large, deeply nested prefixes.
+    /// Without subprefixes this is a plain [`Self::list_all`], the first listing already saw every
+    /// key.
+    pub async fn list_all_parallel(&self) -> anyhow::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut subprefixes = Vec::new();
+
+        let mut stream = self
+            .s3_client
+            .list_objects_v2()
+            .bucket(self.bucket)
+            .prefix(&*self.prefix)
+            .delimiter("/")
+            .into_paginator()
+            .send();
+        while let Some(result) = stream.next().await {
+            let page = result?;
+            keys.extend(
+                page.contents()
+                    .iter()
+                    .filter_map(|object| object.key())
+                    .filter(|key| self.is_listed(key))
+                    .map(str::to_string),
+            );
+            subprefixes.extend(
+                page.common_prefixes()
+                    .iter()
+                    .filter_map(|common| common.prefix().map(str::to_string)),
+            );
+        }
+        if subprefixes.is_empty() {
+            return Ok(keys);
+        }
+
+        debug!(%self.bucket, prefix = %self.prefix, subprefix_count = %subprefixes.len(), "Listing S3 subprefixes in parallel");
+        let nested: Vec<Vec<String>> = stream::iter(subprefixes)
+            .map(|subprefix| async move {
+                S3DirectoryAddr {
+                    s3_client: self.s3_client,
+                    bucket: self.bucket,
+                    prefix: Cow::Owned(subprefix),
+                    skip_directory_markers: self.skip_directory_markers,
+                }
+                .list_all()
+                .await
+            })
+            .buffered(DEFAULT_LIST_CONCURRENCY)
+            .try_collect()
+            .await?;
+        keys.extend(nested.into_iter().flatten());
+        Ok(keys)
+    }
+
+    /// Lists the prefix as a [`S3KeyListing`], for when every key is about to be operated on.
+    pub async fn list_all_addrs(&self) -> anyhow::Result<S3KeyListing<'a>> {
+        Ok(S3KeyListing {
+            s3_client: self.s3_client,
+            bucket: self.bucket,
+            keys: self.list_all().await?,
+        })
+    }
+
+    /// Lists the keys under the prefix that satisfy `predicate`, filtering each page as it arrives
+    /// so rejected keys are never allocated.
+    pub async fn list_all_filtered(
+        &self,
+        predicate: impl Fn(&str) -> bool,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut keys = Vec::new();
+
+        let mut stream = self
+            .s3_client
+            .list_objects_v2()
+            .bucket(self.bucket)
+            .prefix(&*self.prefix)
+            .into_paginator()
+            .send();
+
+        while let Some(result) = stream.next().await {
+            for object in result?.contents() {
+                if let Some(key) = object.key()
+                    && self.is_listed(key)
+                    && predicate(key)
+                {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    pub async fn list_all_with_suffix(&self, suffix: &str) -> anyhow::Result<Vec<String>> {
+        self.list_all_filtered(|key| key.ends_with(suffix)).await
+    }
+
+    /// Lists every object under the prefix along with its size, last modified time and etag.
+    pub async fn list_all_detailed(&self) -> anyhow::Result<Vec<S3ObjectMeta>> {
+        let mut objects = Vec::new();
 
         let mut stream = self
             .s3_client
@@ -228,16 +1667,98 @@ impl<'a> S3DirectoryAddr<'a> {
 
         while let Some(result) = stream.next().await {
             for object in result?.contents() {
-                if let Some(key) = object.key() {
-                    prefix_names.push(key.to_string());
+                if object.key().is_some_and(|key| self.is_listed(key)) {
+                    objects.push(S3ObjectMeta::from(object));
                 }
             }
         }
-        Ok(prefix_names)
+        Ok(objects)
     }
 
-    /// Copy all files from this prefix to another prefix within the same bucket
-    pub async fn copy_into(&self, destination: &S3DirectoryAddr<'_>) -> anyhow::Result<()> {
+    /// Lists every version and delete marker under the prefix, for buckets with versioning enabled.
+    pub async fn list_versions(&self) -> anyhow::Result<Vec<S3ObjectVersion>> {
+        let mut versions = Vec::new();
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+
+        loop {
+            let page = self
+                .s3_client
+                .list_object_versions()
+                .bucket(self.bucket)
+                .prefix(&*self.prefix)
+                .set_key_marker(key_marker.take())
+                .set_version_id_marker(version_id_marker.take())
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(%err,%self.bucket, prefix = %self.prefix, "Failed to list S3 object versions");
+                    anyhow!(err)
+                })?;
+
+            versions.extend(page.versions().iter().map(|version| {
+                S3ObjectVersion {
+                    key: version.key().unwrap_or_default().to_string(),
+                    version_id: version.version_id().unwrap_or_default().to_string(),
+                    is_latest: version.is_latest().unwrap_or(false),
+                    is_delete_marker: false,
+                    size: version.size().unwrap_or_default(),
+                    last_modified: version.last_modified().and_then(|time| {
+                        DateTime::from_timestamp(time.secs(), time.subsec_nanos())
+                    }),
+                    etag: version.e_tag().map(str::to_string),
+                }
+            }));
+            versions.extend(page.delete_markers().iter().map(|marker| {
+                S3ObjectVersion {
+                    key: marker.key().unwrap_or_default().to_string(),
+                    version_id: marker.version_id().unwrap_or_default().to_string(),
+                    is_latest: marker.is_latest().unwrap_or(false),
+                    is_delete_marker: true,
+                    size: 0,
+                    last_modified: marker.last_modified().and_then(|time| {
+                        DateTime::from_timestamp(time.secs(), time.subsec_nanos())
+                    }),
+                    etag: None,
+                }
+            }));
+
+            if !page.is_truncated().unwrap_or(false) {
+                break;
+            }
+            key_marker = page.next_key_marker().map(str::to_string);
+            version_id_marker = page.next_version_id_marker().map(str::to_string);
+        }
+        Ok(versions)
+    }
+
+    /// Copy all files from this prefix to another prefix with server side copies, see
+    /// [`Self::copy_into_with_mode`] for copying between accounts. Objects that fail to copy don't
+    /// stop the rest, but any failure turns the result into a [`CopyError`] carrying the full
+    /// [`CopyReport`].
+    pub async fn copy_into(&self, destination: &S3DirectoryAddr<'_>) -> anyhow::Result<CopyReport> {
+        self.copy_into_with_concurrency(destination, DEFAULT_COPY_CONCURRENCY)
+            .await
+    }
+
+    /// Same as [`Self::copy_into`] but with at most `concurrency` copies in flight, a concurrency of
+    /// 1 (or 0) copies the objects one at a time.
+    pub async fn copy_into_with_concurrency(
+        &self,
+        destination: &S3DirectoryAddr<'_>,
+        concurrency: usize,
+    ) -> anyhow::Result<CopyReport> {
+        self.copy_into_with_mode(destination, concurrency, CopyMode::default())
+            .await
+    }
+
+    /// Same as [`Self::copy_into_with_concurrency`] with the [`CopyMode`] picked by the caller.
+    pub async fn copy_into_with_mode(
+        &self,
+        destination: &S3DirectoryAddr<'_>,
+        concurrency: usize,
+        mode: CopyMode,
+    ) -> anyhow::Result<CopyReport> {
         // Ensure prefixes end with '/' for proper path handling
         let src_prefix = &self.prefix;
 
@@ -248,48 +1769,122 @@ impl<'a> S3DirectoryAddr<'a> {
             src_prefix = %src_prefix,
             dest_bucket = %destination.bucket,
             dest_prefix = %dest_prefix,
+            ?mode,
             "Copying files between S3 prefixes"
         );
-        let file_list = self.list_all().await?;
-
-        let file_count = stream::iter(file_list)
-            .map(|source_key| {
-                let s3_client = self.s3_client.clone();
-                let bucket = self.bucket.to_string();
-                let dest_bucket = destination.bucket.to_string();
-                let dest_prefix = destination.prefix.to_string();
-                let src_prefix = self.prefix.to_string();
-
-                async move {
-                    let relative_path = source_key.strip_prefix(&src_prefix).unwrap_or(&source_key);
-                    let destination_key = format!("{}{}", dest_prefix, relative_path);
-
-                    debug!(src_key = %source_key, dest_key = %destination_key, "Copying object");
-
-                    // Perform the copy operation
-                    let _copy_res = s3_client
-                        .copy_object()
-                        .bucket(dest_bucket)
-                        .key(&destination_key)
-                        .copy_source(format!("{}/{}", bucket, source_key))
-                        .send()
-                        .await;
-                    info!(%destination_key,"Successfully copied file")
+
+        // Keys are copied as the listing pages arrive, so nothing but the in flight copies is held
+        // in memory and nothing is cloned per object beyond its destination key.
+        let report = self
+            .list_stream()
+            .map_ok(|source_key| async move {
+                let relative_path = source_key
+                    .strip_prefix(&**src_prefix)
+                    .unwrap_or(&source_key);
+                let destination_key = format!("{}{}", dest_prefix, relative_path);
+
+                debug!(src_key = %source_key, dest_key = %destination_key, "Copying object");
+
+                let source = S3Addr::new(self.s3_client, self.bucket, &source_key);
+                let dest = S3Addr::new(destination.s3_client, destination.bucket, &destination_key);
+                match source.copy_to_with_mode(&dest, mode).await {
+                    Ok(()) => {
+                        info!(%destination_key,"Successfully copied file");
+                        Ok(None)
+                    }
+                    Err(err) => {
+                        error!(%err, %source_key, %destination_key, "Failed to copy file");
+                        Ok(Some((source_key, err.to_string())))
+                    }
                 }
             })
-            .buffer_unordered(25)
-            .count()
-            .await;
-
+            .try_buffer_unordered(concurrency.max(1))
+            .try_fold(CopyReport::default(), |mut report, failure| async move {
+                report.total += 1;
+                match failure {
+                    None => report.succeeded += 1,
+                    Some(failure) => report.failed.push(failure),
+                }
+                Ok(report)
+            })
+            .await?;
         info!(
-            %file_count,
+            total = %report.total,
+            succeeded = %report.succeeded,
+            failed = %report.failed.len(),
             src_bucket = %self.bucket,
             src_prefix = %src_prefix,
             dest_bucket = %destination.bucket,
             dest_prefix = %dest_prefix,
-            "Successfully copied files between S3 prefixes"
+            "Finished copying files between S3 prefixes"
         );
 
-        Ok(())
+        if !report.failed.is_empty() {
+            return Err(CopyError { report }.into());
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_empty_prefix_deletes_are_refused() {
+        let s3_client = S3Credentials::new("us-east-1", "http://localhost:1", "key", "secret")
+            .make_s3_client()
+            .await;
+        let whole_bucket = S3DirectoryAddr::new(&s3_client, "bucket", "");
+        assert_eq!(whole_bucket.prefix, "");
+        for result in [
+            whole_bucket.delete_all().await,
+            whole_bucket.delete_all_filtered(|_| true).await.map(drop),
+        ] {
+            assert!(result.unwrap_err().is::<EmptyPrefixDeleteError>());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_part_reader_splits_on_part_size() {
+        let body = ByteStream::from((0u8..12).collect::<Vec<u8>>());
+        let mut reader = PartReader::new(body, 5);
+        let mut parts = Vec::new();
+        while let Some(part) = reader.next_part().await.unwrap() {
+            parts.push(part);
+        }
+        assert_eq!(
+            parts,
+            vec![vec![0, 1, 2, 3, 4], vec![5, 6, 7, 8, 9], vec![10, 11]]
+        );
+    }
+
+    #[test]
+    fn test_encode_copy_source_escapes_key_segments() {
+        assert_eq!(
+            encode_copy_source("bucket", "reports/2024 Q1/summary (final).json"),
+            "bucket/reports/2024%20Q1/summary%20%28final%29.json"
+        );
+        assert_eq!(
+            encode_copy_source("bucket", "a+b/100%/café"),
+            "bucket/a%2Bb/100%25/caf%C3%A9"
+        );
+    }
+
+    #[test]
+    fn test_directory_prefix_normalization() {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(aws_sdk_s3::config::BehaviorVersion::latest())
+            .build();
+        let s3_client = S3Client::from_conf(config);
+        assert_eq!(S3DirectoryAddr::new(&s3_client, "bucket", "").prefix, "");
+        assert_eq!(
+            S3DirectoryAddr::new(&s3_client, "bucket", "reports").prefix,
+            "reports/"
+        );
+        assert_eq!(
+            S3DirectoryAddr::new(&s3_client, "bucket", "reports/").prefix,
+            "reports/"
+        );
     }
 }
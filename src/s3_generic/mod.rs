@@ -1,28 +1,61 @@
 use core::fmt;
 use std::{fmt::Debug, sync::LazyLock};
 
+use anyhow::anyhow;
 use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3::{Client, config::Credentials};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::misc::is_env_var_true;
 
 pub mod cannonical_location;
+pub mod checksum;
+pub mod compression;
 pub mod fetchers_and_getters;
 pub mod s3_uri;
+pub mod sync;
+#[cfg(feature = "testsupport")]
+pub mod testsupport;
+pub mod upload_options;
 
+#[derive(Clone)]
 pub struct S3Credentials {
     pub cloud_region: String,
     pub endpoint: String,
-    access_key: String,
-    secret_key: String,
+    /// Address buckets as `{endpoint}/{bucket}` rather than `{bucket}.{endpoint}`, needed for MinIO
+    /// and older Ceph deployments.
+    pub force_path_style: bool,
+    keys: S3KeySource,
+}
+
+/// Where the client gets its signing keys from.
+#[derive(Clone)]
+enum S3KeySource {
+    Static {
+        access_key: String,
+        secret_key: String,
+        session_token: Option<String>,
+    },
+    /// Defer to the AWS default provider chain (env, profile, SSO, instance/task role).
+    DefaultChain,
 }
 
 impl Debug for S3Credentials {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("S3Credentials")
+        let mut debug = f.debug_struct("S3Credentials");
+        debug
             .field("cloud_region", &self.cloud_region)
             .field("endpoint", &self.endpoint)
-            .field("access_key", &"***")
-            .field("secret_key", &"***")
-            .finish()
+            .field("force_path_style", &self.force_path_style);
+        match &self.keys {
+            S3KeySource::Static { session_token, .. } => debug
+                .field("access_key", &"***")
+                .field("secret_key", &"***")
+                .field("session_token", &session_token.as_ref().map(|_| "***")),
+            S3KeySource::DefaultChain => debug.field("keys", &"default provider chain"),
+        };
+        debug.finish()
     }
 }
 
@@ -34,27 +67,234 @@ impl PartialEq for S3Credentials {
     }
 }
 impl S3Credentials {
+    pub fn new(
+        cloud_region: impl Into<String>,
+        endpoint: impl Into<String>,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        S3Credentials {
+            cloud_region: cloud_region.into(),
+            endpoint: endpoint.into(),
+            force_path_style: false,
+            keys: S3KeySource::Static {
+                access_key: access_key.into(),
+                secret_key: secret_key.into(),
+                session_token: None,
+            },
+        }
+    }
+    /// Uses the AWS default credential provider chain instead of hardcoded keys, which picks up
+    /// instance profiles, IAM roles and SSO sessions.
+    pub fn from_default_chain(
+        cloud_region: impl Into<String>,
+        endpoint: impl Into<String>,
+    ) -> Self {
+        S3Credentials {
+            cloud_region: cloud_region.into(),
+            endpoint: endpoint.into(),
+            force_path_style: false,
+            keys: S3KeySource::DefaultChain,
+        }
+    }
+    /// Attaches a temporary session token, as handed out by STS or an assumed role. Has no effect
+    /// on credentials using the default provider chain.
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        if let S3KeySource::Static { session_token, .. } = &mut self.keys {
+            *session_token = Some(token.into());
+        }
+        self
+    }
+    pub fn with_force_path_style(mut self, force_path_style: bool) -> Self {
+        self.force_path_style = force_path_style;
+        self
+    }
+    /// Whether both credentials reach the same account, i.e. the same endpoint and region with the
+    /// same access key, so objects can be server side copied between them.
+    pub fn same_account(&self, other: &S3Credentials) -> bool {
+        let keys_match = match (&self.keys, &other.keys) {
+            (
+                S3KeySource::Static { access_key, .. },
+                S3KeySource::Static {
+                    access_key: other_access_key,
+                    ..
+                },
+            ) => access_key == other_access_key,
+            // Both resolve from the same environment in this process.
+            (S3KeySource::DefaultChain, S3KeySource::DefaultChain) => true,
+            _ => false,
+        };
+        keys_match && self.matches_endpoint_and_region(&other.endpoint, &other.cloud_region)
+    }
     fn matches_endpoint_and_region(&self, endpoint: &str, region: &str) -> bool {
         self.endpoint == endpoint && self.cloud_region == region
     }
     pub async fn make_s3_client(&self) -> Client {
+        self.make_s3_client_with(|builder| builder).await
+    }
+    /// Builds a client, letting `customize` adjust the S3 config builder (timeouts, retry config,
+    /// etc) before the client is created.
+    pub async fn make_s3_client_with<F>(&self, customize: F) -> Client
+    where
+        F: FnOnce(aws_sdk_s3::config::Builder) -> aws_sdk_s3::config::Builder,
+    {
+        self.build_client(&self.cloud_region, &self.endpoint, customize)
+            .await
+    }
+    /// Builds a client with the same keys but a different region, and optionally a different
+    /// endpoint, for keys that have access across several regions.
+    pub async fn make_s3_client_for_region(&self, region: &str, endpoint: Option<&str>) -> Client {
+        self.build_client(region, endpoint.unwrap_or(&self.endpoint), |builder| {
+            builder
+        })
+        .await
+    }
+    async fn build_client<F>(&self, region: &str, endpoint: &str, customize: F) -> Client
+    where
+        F: FnOnce(aws_sdk_s3::config::Builder) -> aws_sdk_s3::config::Builder,
+    {
         // info!("Creating S3 client");
-        let creds = Credentials::new(
-            &self.access_key,
-            &self.secret_key,
-            None, // no session token
-            None, // no expiration
-            "manual",
-        );
-
+        if !region_matches_endpoint(region, endpoint) {
+            warn!(
+                %region,
+                %endpoint,
+                "S3 region does not appear in the endpoint host, requests may fail to authenticate"
+            );
+        }
         // Start from the env-loader so we still pick up other settings (timeouts, retry, etc)
-        let cfg_loader = aws_config::defaults(BehaviorVersion::latest())
-            .region(Region::new(self.cloud_region.clone()))
-            .credentials_provider(creds)
-            .endpoint_url(&self.endpoint);
+        let mut cfg_loader = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(region.to_string()))
+            .endpoint_url(endpoint);
+        if let S3KeySource::Static {
+            access_key,
+            secret_key,
+            session_token,
+        } = &self.keys
+        {
+            let creds = Credentials::new(
+                access_key,
+                secret_key,
+                session_token.clone(),
+                None, // no expiration
+                "manual",
+            );
+            cfg_loader = cfg_loader.credentials_provider(creds);
+        }
 
         let sdk_config = cfg_loader.load().await;
-        Client::new(&sdk_config)
+        let s3_config_builder =
+            aws_sdk_s3::config::Builder::from(&sdk_config).force_path_style(self.force_path_style);
+        let s3_config = customize(s3_config_builder).build();
+        Client::from_conf(s3_config)
+    }
+}
+
+/// Sanity check that the region matches the one in the endpoint host, e.g. `sfo3` in
+/// `https://sfo3.digitaloceanspaces.com`. A mismatch usually means a typo in one of the two. Hosts
+/// that don't encode a region (localhost, IPs, MinIO, LocalStack) always pass.
+fn region_matches_endpoint(region: &str, endpoint: &str) -> bool {
+    let without_scheme = endpoint
+        .strip_prefix("https://")
+        .or_else(|| endpoint.strip_prefix("http://"))
+        .unwrap_or(endpoint);
+    let host = without_scheme.split(['/', ':']).next().unwrap_or_default();
+    endpoint_host_region(host).is_none_or(|host_region| host_region == region)
+}
+
+/// The region of the `<region>.<provider>` hosts we know the shape of: DigitalOcean Spaces and
+/// regional AWS endpoints like `s3.us-west-2.amazonaws.com`.
+fn endpoint_host_region(host: &str) -> Option<&str> {
+    let labels: Vec<&str> = host.split('.').collect();
+    match labels.as_slice() {
+        [.., region, "digitaloceanspaces", "com"] => Some(region),
+        // The global `s3.amazonaws.com` has no region label.
+        [.., region, "amazonaws", "com"] if *region != "s3" => Some(region),
+        _ => None,
+    }
+}
+
+pub const DEFAULT_S3_REGION: &str = "sfo3";
+pub const DEFAULT_S3_ENDPOINT: &str = "https://sfo3.digitaloceanspaces.com";
+
+/// Builds [`S3Credentials`] at runtime, for setups that don't warrant an [`S3EnvNames`] type.
+/// Anything left unset falls back to the standard AWS env vars (`AWS_REGION`, `AWS_ENDPOINT_URL`)
+/// and then to the DigitalOcean defaults, and without keys the default AWS credential chain is used.
+#[derive(Default)]
+pub struct S3CredentialsBuilder {
+    region: Option<String>,
+    endpoint: Option<String>,
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    session_token: Option<String>,
+    force_path_style: bool,
+}
+
+impl Debug for S3CredentialsBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3CredentialsBuilder")
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .field("access_key", &self.access_key.as_ref().map(|_| "***"))
+            .field("secret_key", &self.secret_key.as_ref().map(|_| "***"))
+            .field("session_token", &self.session_token.as_ref().map(|_| "***"))
+            .field("force_path_style", &self.force_path_style)
+            .finish()
+    }
+}
+
+impl S3CredentialsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+    pub fn with_access_key(mut self, access_key: impl Into<String>) -> Self {
+        self.access_key = Some(access_key.into());
+        self
+    }
+    pub fn with_secret_key(mut self, secret_key: impl Into<String>) -> Self {
+        self.secret_key = Some(secret_key.into());
+        self
+    }
+    pub fn with_session_token(mut self, session_token: impl Into<String>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+    pub fn with_force_path_style(mut self, force_path_style: bool) -> Self {
+        self.force_path_style = force_path_style;
+        self
+    }
+    /// Fails when only one of the access and secret keys was given.
+    pub fn build(self) -> anyhow::Result<S3Credentials> {
+        let cloud_region = self
+            .region
+            .or_else(|| std::env::var("AWS_REGION").ok())
+            .unwrap_or_else(|| DEFAULT_S3_REGION.to_string());
+        let endpoint = self
+            .endpoint
+            .or_else(|| std::env::var("AWS_ENDPOINT_URL").ok())
+            .unwrap_or_else(|| DEFAULT_S3_ENDPOINT.to_string());
+        let credentials = match (self.access_key, self.secret_key) {
+            (Some(access_key), Some(secret_key)) => {
+                S3Credentials::new(cloud_region, endpoint, access_key, secret_key)
+            }
+            (None, None) => S3Credentials::from_default_chain(cloud_region, endpoint),
+            (None, Some(_)) => {
+                return Err(anyhow!("S3 secret key was given without an access key"));
+            }
+            (Some(_), None) => return Err(anyhow!("S3 access key was given without a secret key")),
+        };
+        let credentials = credentials.with_force_path_style(self.force_path_style);
+        Ok(match self.session_token {
+            Some(session_token) => credentials.with_session_token(session_token),
+            None => credentials,
+        })
     }
 }
 
@@ -63,10 +303,28 @@ pub trait S3EnvNames {
     const ENDPOINT_ENV: &str;
     const ACCESS_ENV: &str;
     const SECRET_ENV: &str;
-    const DEFAULT_S3_REGION: &str = "sf03";
-    const DEFAULT_S3_ENDPOINT: &str = "https://sfo3.digitaloceanspaces.com";
+    /// Optional env var holding a temporary session token, ignored when unset.
+    const SESSION_TOKEN_ENV: Option<&str> = None;
+    /// Optional env var that switches on path-style addressing when truthy.
+    const FORCE_PATH_STYLE_ENV: Option<&str> = None;
+    const DEFAULT_S3_REGION: &str = DEFAULT_S3_REGION;
+    const DEFAULT_S3_ENDPOINT: &str = DEFAULT_S3_ENDPOINT;
+}
+#[derive(Debug, Error)]
+#[error("S3 {kind} env var was not set: {name}")]
+pub struct MissingEnv {
+    pub kind: &'static str,
+    pub name: &'static str,
 }
+
 fn init_from_env_vars<T: S3EnvNames>() -> S3Credentials {
+    try_init_from_env_vars::<T>().unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Reads the credentials from the env vars named by `T`, falling back to the defaults for region
+/// and endpoint. Only setting one of the access and secret keys is an error, rather than the panic
+/// [`make_s3_lazylock`] gives.
+pub fn try_init_from_env_vars<T: S3EnvNames>() -> Result<S3Credentials, MissingEnv> {
     let cloud_region = std::env::var(T::REGION_ENV).unwrap_or_else(|_| {
         println!("S3 region not set, using default: {}", T::DEFAULT_S3_REGION);
         T::DEFAULT_S3_REGION.to_string()
@@ -78,18 +336,99 @@ fn init_from_env_vars<T: S3EnvNames>() -> S3Credentials {
         );
         T::DEFAULT_S3_ENDPOINT.to_string()
     });
-    let access_key = std::env::var(T::ACCESS_ENV)
-        .unwrap_or_else(|_| panic!("S3 access key env var was not set: {}", T::ACCESS_ENV));
-    let secret_key = std::env::var(T::SECRET_ENV)
-        .unwrap_or_else(|_| panic!("S3 secret key env var was not set: {}", T::SECRET_ENV));
+    let force_path_style = T::FORCE_PATH_STYLE_ENV.is_some_and(is_env_var_true);
+    let (access_key, secret_key) = match (
+        std::env::var(T::ACCESS_ENV),
+        std::env::var(T::SECRET_ENV),
+    ) {
+        (Ok(access_key), Ok(secret_key)) => (access_key, secret_key),
+        (Err(_), Err(_)) => {
+            println!(
+                "S3 access and secret key env vars not set ({}, {}), using the default AWS credential chain",
+                T::ACCESS_ENV,
+                T::SECRET_ENV
+            );
+            return Ok(S3Credentials::from_default_chain(cloud_region, endpoint)
+                .with_force_path_style(force_path_style));
+        }
+        (Err(_), Ok(_)) => {
+            return Err(MissingEnv {
+                kind: "access key",
+                name: T::ACCESS_ENV,
+            });
+        }
+        (Ok(_), Err(_)) => {
+            return Err(MissingEnv {
+                kind: "secret key",
+                name: T::SECRET_ENV,
+            });
+        }
+    };
+    let credentials = S3Credentials::new(cloud_region, endpoint, access_key, secret_key)
+        .with_force_path_style(force_path_style);
 
-    S3Credentials {
-        cloud_region,
-        endpoint,
-        access_key,
-        secret_key,
-    }
+    let credentials = match T::SESSION_TOKEN_ENV.and_then(|name| std::env::var(name).ok()) {
+        Some(session_token) => credentials.with_session_token(session_token),
+        None => credentials,
+    };
+    Ok(credentials)
 }
 pub const fn make_s3_lazylock<T: S3EnvNames>() -> LazyLock<S3Credentials> {
     LazyLock::new(init_from_env_vars::<T>)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_account() {
+        let credentials = S3Credentials::new("sfo3", DEFAULT_S3_ENDPOINT, "access", "secret");
+        assert!(credentials.same_account(&credentials.clone().with_force_path_style(true)));
+        assert!(!credentials.same_account(&S3Credentials::new(
+            "sfo3",
+            DEFAULT_S3_ENDPOINT,
+            "other-access",
+            "secret",
+        )));
+        assert!(!credentials.same_account(&S3Credentials::new(
+            "sfo3",
+            "https://nyc3.digitaloceanspaces.com",
+            "access",
+            "secret",
+        )));
+    }
+
+    #[test]
+    fn test_region_matches_endpoint() {
+        assert!(region_matches_endpoint(
+            "sfo3",
+            "https://sfo3.digitaloceanspaces.com"
+        ));
+        assert!(region_matches_endpoint(
+            "us-west-2",
+            "https://s3.us-west-2.amazonaws.com/"
+        ));
+        assert!(!region_matches_endpoint(
+            "sf03",
+            "https://sfo3.digitaloceanspaces.com"
+        ));
+        assert!(!region_matches_endpoint(
+            "us-east-1",
+            "https://s3.us-west-2.amazonaws.com"
+        ));
+        assert!(region_matches_endpoint(
+            "us-east-1",
+            "http://localhost:9000"
+        ));
+        assert!(region_matches_endpoint(
+            "us-east-1",
+            "http://127.0.0.1:9000"
+        ));
+        assert!(region_matches_endpoint("garage", "https://s3.example.com"));
+        assert!(region_matches_endpoint(
+            "us-east-1",
+            "https://s3.amazonaws.com"
+        ));
+    }
+}
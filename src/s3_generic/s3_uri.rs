@@ -1,10 +1,15 @@
+use std::borrow::Cow;
 use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
 
+use anyhow::anyhow;
+use aws_sdk_s3::presigning::PresigningConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::s3_generic::S3Credentials;
+use crate::s3_generic::{S3Credentials, S3CredentialsBuilder};
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone)]
 pub struct S3Location {
@@ -18,7 +23,11 @@ pub struct S3Location {
 pub struct S3LocationWithCredentials {
     pub key: String,
     pub bucket: String,
-    credentials: &'static S3Credentials,
+    /// Query string without the leading `?`, kept so parsed URLs print back unchanged.
+    pub query: Option<String>,
+    /// Fragment without the leading `#`.
+    pub fragment: Option<String>,
+    credentials: Cow<'static, S3Credentials>,
 }
 
 impl From<S3LocationWithCredentials> for S3Location {
@@ -44,7 +53,9 @@ impl S3LocationWithCredentials {
         S3LocationWithCredentials {
             key: key.to_string(),
             bucket: bucket.to_string(),
-            credentials,
+            query: None,
+            fragment: None,
+            credentials: Cow::Borrowed(credentials),
         }
     }
     pub fn try_from_location_and_credentials(
@@ -52,14 +63,66 @@ impl S3LocationWithCredentials {
         credentials: &'static S3Credentials,
     ) -> Result<Self, MismatchedRegion> {
         if loc.endpoint == credentials.endpoint && loc.region == credentials.cloud_region {
-            return Ok(S3LocationWithCredentials {
-                key: loc.key.clone(),
-                bucket: loc.bucket.clone(),
+            return Ok(Self::from_key_bucket_and_credentials(
+                &loc.key,
+                &loc.bucket,
                 credentials,
-            });
+            ));
         }
         Err(MismatchedRegion {})
     }
+    /// Parses `s3://{bucket}/{key…}`, virtual hosted `http(s)://{bucket}.{region}.{provider}/{key…}`
+    /// and path style `http(s)://{host}/{bucket}/{key…}` URLs, taking the region and endpoint from
+    /// the host. Keys come from the AWS default provider chain, and everything the URL doesn't say
+    /// (the region of a `localhost` endpoint, anything for `s3://`) falls back the same way
+    /// [`S3CredentialsBuilder::build`] does. Round-trips with `to_string()`, query and fragment
+    /// included.
+    pub fn parse(url: &str) -> anyhow::Result<Self> {
+        let parsed = ParsedUrl::parse(url, false)?;
+        let mut builder = S3CredentialsBuilder::new();
+        if let Some(origin) = &parsed.origin {
+            builder = builder
+                .with_endpoint(&origin.endpoint)
+                .with_force_path_style(origin.path_style);
+            if let Some(region) = &origin.region {
+                builder = builder.with_region(region);
+            }
+        }
+        Ok(parsed.into_location(Cow::Owned(builder.build()?)))
+    }
+
+    /// Same as [`Self::parse`] but signs with `credentials`, failing with [`MismatchedRegion`]
+    /// when the URL points at a different endpoint or region.
+    pub fn parse_with_credentials(
+        url: &str,
+        credentials: &'static S3Credentials,
+    ) -> anyhow::Result<Self> {
+        let parsed = ParsedUrl::parse(url, credentials.force_path_style)?;
+        if let Some(origin) = &parsed.origin {
+            let region_matches = origin
+                .region
+                .as_ref()
+                .is_none_or(|region| *region == credentials.cloud_region);
+            if origin.endpoint != credentials.endpoint || !region_matches {
+                return Err(MismatchedRegion {}.into());
+            }
+        }
+        Ok(parsed.into_location(Cow::Borrowed(credentials)))
+    }
+
+    /// Produces a presigned GET URL valid for `expires_in`, which works for private objects where
+    /// the plain `to_string()` URL would 403. Builds a short lived client from the credentials.
+    pub async fn presigned_url(&self, expires_in: Duration) -> anyhow::Result<String> {
+        let s3_client = self.credentials.make_s3_client().await;
+        let presigned = s3_client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .presigned(PresigningConfig::expires_in(expires_in)?)
+            .await
+            .map_err(|err| anyhow!(err))?;
+        Ok(presigned.uri().to_string())
+    }
 }
 // https://examplebucket.sfo3.digitaloceanspaces.com/this/is/the/file/key
 //
@@ -70,17 +133,14 @@ impl S3LocationWithCredentials {
 // key: this/is/the/file/key
 impl fmt::Display for S3Location {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // strip off the "https://" prefix on the endpoint to get e.g. "sfo3.digitaloceanspaces.com"
-        let host_part = self
-            .endpoint
-            .strip_prefix("https://")
-            .unwrap_or(&self.endpoint);
+        // split the endpoint into e.g. "https" and "sfo3.digitaloceanspaces.com"
+        let (scheme, host_part) = split_scheme(&self.endpoint);
 
         // make sure we don’t end up with duplicate or missing slashes
         let key_part = self.key.trim_start_matches('/');
 
         // build "https://{bucket}.{host_part}/{key…}"
-        let mut url = format!("https://{}.{}", self.bucket, host_part);
+        let mut url = format!("{scheme}://{}.{}", self.bucket, host_part);
         if !key_part.is_empty() {
             url.push('/');
             url.push_str(key_part);
@@ -91,26 +151,154 @@ impl fmt::Display for S3Location {
 
 impl fmt::Display for S3LocationWithCredentials {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // strip off the "https://" prefix on the endpoint to get e.g. "sfo3.digitaloceanspaces.com"
-        let host_part = self
-            .credentials
-            .endpoint
-            .strip_prefix("https://")
-            .unwrap_or(&self.credentials.endpoint);
+        // split the endpoint into e.g. "https" and "sfo3.digitaloceanspaces.com"
+        let (scheme, host_part) = split_scheme(&self.credentials.endpoint);
 
         // make sure we don’t end up with duplicate or missing slashes
         let key_part = self.key.trim_start_matches('/');
 
-        // build "https://{bucket}.{host_part}/{key…}"
-        let mut url = format!("https://{}.{}", self.bucket, host_part);
+        // build "https://{bucket}.{host_part}/{key…}", or "https://{host_part}/{bucket}/{key…}"
+        // for backends that need path style addressing
+        let mut url = if self.credentials.force_path_style {
+            format!("{scheme}://{}/{}", host_part, self.bucket)
+        } else {
+            format!("{scheme}://{}.{}", self.bucket, host_part)
+        };
         if !key_part.is_empty() {
             url.push('/');
             url.push_str(key_part);
         }
+        if let Some(query) = &self.query {
+            url.push('?');
+            url.push_str(query);
+        }
+        if let Some(fragment) = &self.fragment {
+            url.push('#');
+            url.push_str(fragment);
+        }
         write!(f, "{url}")
     }
 }
 
+/// Splits `https://host` into `("https", "host")`, endpoints without a scheme are taken as https.
+fn split_scheme(endpoint: &str) -> (&str, &str) {
+    endpoint.split_once("://").unwrap_or(("https", endpoint))
+}
+
+/// Where a parsed http(s) URL points, `s3://` URLs don't say.
+struct UrlOrigin {
+    endpoint: String,
+    region: Option<String>,
+    path_style: bool,
+}
+
+struct ParsedUrl {
+    bucket: String,
+    key: String,
+    query: Option<String>,
+    fragment: Option<String>,
+    origin: Option<UrlOrigin>,
+}
+
+impl ParsedUrl {
+    /// With `path_style` set, http(s) hosts are never read as `{bucket}.{endpoint}`.
+    fn parse(url: &str, path_style: bool) -> Result<Self, S3DecodeError> {
+        let (url, fragment) = match url.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment.to_string())),
+            None => (url, None),
+        };
+        let (url, query) = match url.split_once('?') {
+            Some((url, query)) => (url, Some(query.to_string())),
+            None => (url, None),
+        };
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or(S3DecodeError::InvalidLocation)?;
+
+        let (bucket, key, origin) = match scheme {
+            "s3" => {
+                let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+                (bucket, key, None)
+            }
+            "http" | "https" => {
+                let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+                match split_virtual_host(host).filter(|_| !path_style) {
+                    Some((bucket, endpoint_host, region)) => {
+                        let origin = UrlOrigin {
+                            endpoint: format!("{scheme}://{endpoint_host}"),
+                            region: Some(region.to_string()),
+                            path_style: false,
+                        };
+                        (bucket, path, Some(origin))
+                    }
+                    None => {
+                        let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+                        let origin = UrlOrigin {
+                            endpoint: format!("{scheme}://{host}"),
+                            region: path_style_region(host).map(str::to_string),
+                            path_style: true,
+                        };
+                        (bucket, key, Some(origin))
+                    }
+                }
+            }
+            _ => return Err(S3DecodeError::InvalidLocation),
+        };
+        if bucket.is_empty() {
+            return Err(S3DecodeError::InvalidLocation);
+        }
+        Ok(ParsedUrl {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            query,
+            fragment,
+            origin,
+        })
+    }
+
+    fn into_location(self, credentials: Cow<'static, S3Credentials>) -> S3LocationWithCredentials {
+        S3LocationWithCredentials {
+            key: self.key,
+            bucket: self.bucket,
+            query: self.query,
+            fragment: self.fragment,
+            credentials,
+        }
+    }
+}
+
+/// Hosts that can't hold a bucket subdomain: ports, IPs and short names like `localhost`.
+fn is_bare_host(host: &str) -> bool {
+    host.contains(':') || host.parse::<IpAddr>().is_ok() || host.split('.').count() < 3
+}
+
+/// Splits `{bucket}.{region}.{provider}` (or AWS's `{bucket}.s3.{region}.amazonaws.com`) into the
+/// bucket, the endpoint host and the region, `None` for hosts that put the bucket in the path.
+fn split_virtual_host(host: &str) -> Option<(&str, &str, &str)> {
+    if is_bare_host(host) || host.starts_with("s3.") {
+        return None;
+    }
+    let (bucket, endpoint_host) = host.split_once('.')?;
+    Some((bucket, endpoint_host, host_region(endpoint_host)))
+}
+
+/// The region of a path style host, only known for `s3.{region}.amazonaws.com` style hosts.
+fn path_style_region(host: &str) -> Option<&str> {
+    host.starts_with("s3.").then(|| host_region(host))
+}
+
+/// The region label of an endpoint host, `sfo3` of `sfo3.digitaloceanspaces.com` and `us-west-2`
+/// of `s3.us-west-2.amazonaws.com`. The global `s3.amazonaws.com` is `us-east-1`.
+fn host_region(endpoint_host: &str) -> &str {
+    let labels: Vec<&str> = endpoint_host.split('.').collect();
+    match labels.as_slice() {
+        ["s3", "amazonaws", "com"] => "us-east-1",
+        ["s3", region, ..] => region,
+        [region, ..] => region,
+        [] => "",
+    }
+}
+
 //
 // Try to parse the URL back into its components.
 // Expects URLs of the form
@@ -157,3 +345,116 @@ impl TryFrom<String> for S3Location {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leak_credentials(credentials: S3Credentials) -> &'static S3Credentials {
+        Box::leak(Box::new(credentials))
+    }
+
+    #[test]
+    fn test_parse_takes_region_and_endpoint_from_host() {
+        let url = "https://examplebucket.sfo3.digitaloceanspaces.com/this/is/the/file/key";
+        let location = S3LocationWithCredentials::parse(url).unwrap();
+        assert_eq!(location.bucket, "examplebucket");
+        assert_eq!(location.key, "this/is/the/file/key");
+        assert_eq!(location.credentials.cloud_region, "sfo3");
+        assert_eq!(
+            location.credentials.endpoint,
+            "https://sfo3.digitaloceanspaces.com"
+        );
+        assert_eq!(location.to_string(), url);
+
+        let aws = S3LocationWithCredentials::parse(
+            "https://examplebucket.s3.us-west-2.amazonaws.com/key.json",
+        )
+        .unwrap();
+        assert_eq!(aws.credentials.cloud_region, "us-west-2");
+        assert_eq!(
+            aws.credentials.endpoint,
+            "https://s3.us-west-2.amazonaws.com"
+        );
+
+        let aws_path_style = S3LocationWithCredentials::parse(
+            "https://s3.eu-west-1.amazonaws.com/examplebucket/key",
+        )
+        .unwrap();
+        assert_eq!(aws_path_style.bucket, "examplebucket");
+        assert_eq!(aws_path_style.credentials.cloud_region, "eu-west-1");
+    }
+
+    #[test]
+    fn test_parse_round_trips_query_and_fragment() {
+        let url = "https://examplebucket.sfo3.digitaloceanspaces.com/key.json?versionId=3&x=y#part";
+        let location = S3LocationWithCredentials::parse(url).unwrap();
+        assert_eq!(location.key, "key.json");
+        assert_eq!(location.query.as_deref(), Some("versionId=3&x=y"));
+        assert_eq!(location.fragment.as_deref(), Some("part"));
+        assert_eq!(location.to_string(), url);
+    }
+
+    #[test]
+    fn test_parse_http_path_style() {
+        let url = "http://localhost:9000/examplebucket/some/key.json";
+        let location = S3LocationWithCredentials::parse(url).unwrap();
+        assert_eq!(location.bucket, "examplebucket");
+        assert_eq!(location.key, "some/key.json");
+        assert_eq!(location.credentials.endpoint, "http://localhost:9000");
+        assert!(location.credentials.force_path_style);
+        assert_eq!(location.to_string(), url);
+
+        let ip = S3LocationWithCredentials::parse("http://127.0.0.1/examplebucket/key").unwrap();
+        assert_eq!(ip.bucket, "examplebucket");
+        assert_eq!(ip.to_string(), "http://127.0.0.1/examplebucket/key");
+
+        assert!(S3LocationWithCredentials::parse("http://localhost:9000/").is_err());
+        assert!(S3LocationWithCredentials::parse("ftp://examplebucket/key").is_err());
+    }
+
+    #[test]
+    fn test_parse_with_credentials_checks_endpoint_and_region() {
+        let credentials = leak_credentials(S3Credentials::new(
+            "sfo3",
+            "https://sfo3.digitaloceanspaces.com",
+            "access",
+            "secret",
+        ));
+        let url = "https://examplebucket.sfo3.digitaloceanspaces.com/this/is/the/file/key";
+        let location = S3LocationWithCredentials::parse_with_credentials(url, credentials).unwrap();
+        assert_eq!(location.bucket, "examplebucket");
+        assert_eq!(location.key, "this/is/the/file/key");
+        assert_eq!(location.to_string(), url);
+
+        let other_region = "https://examplebucket.nyc3.digitaloceanspaces.com/key";
+        assert!(
+            S3LocationWithCredentials::parse_with_credentials(other_region, credentials).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_with_credentials_s3_scheme_and_path_style() {
+        let credentials = leak_credentials(
+            S3Credentials::new("us-east-1", "https://minio.example.com", "access", "secret")
+                .with_force_path_style(true),
+        );
+        let location = S3LocationWithCredentials::parse_with_credentials(
+            "s3://examplebucket/some/key.json",
+            credentials,
+        )
+        .unwrap();
+        assert_eq!(location.bucket, "examplebucket");
+        assert_eq!(location.key, "some/key.json");
+
+        let path_style = location.to_string();
+        assert_eq!(
+            path_style,
+            "https://minio.example.com/examplebucket/some/key.json"
+        );
+        let reparsed =
+            S3LocationWithCredentials::parse_with_credentials(&path_style, credentials).unwrap();
+        assert_eq!(reparsed.bucket, "examplebucket");
+        assert_eq!(reparsed.key, "some/key.json");
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use futures_util::{StreamExt, stream};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+
+use crate::s3_generic::fetchers_and_getters::{
+    CopyMode, DEFAULT_COPY_CONCURRENCY, MAX_DELETE_BATCH_SIZE, S3Addr, S3DirectoryAddr,
+    S3ObjectMeta,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SyncReport {
+    pub copied: usize,
+    pub deleted: usize,
+    pub skipped: usize,
+    /// Pairs of (key, error message) for every object that could not be copied or deleted, the
+    /// rest of the sync carries on past them.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Multipart uploads get an ETag of `<md5 of the part md5s>-<part count>` rather than the MD5 of
+/// the body, so it never matches the ETag of the same bytes uploaded any other way.
+fn is_multipart_etag(etag: &str) -> bool {
+    etag.trim_matches('"').contains('-')
+}
+
+/// Objects are treated as unchanged when their sizes and ETags match, objects missing an ETag on
+/// either side are always copied. ETags can't be compared when either side was a multipart
+/// upload, so those count as unchanged when the destination was written no earlier than the
+/// source.
+fn is_unchanged(source: &S3ObjectMeta, destination: &S3ObjectMeta) -> bool {
+    if source.size != destination.size {
+        return false;
+    }
+    match (&source.etag, &destination.etag) {
+        (Some(source_etag), Some(destination_etag))
+            if is_multipart_etag(source_etag) || is_multipart_etag(destination_etag) =>
+        {
+            match (source.last_modified, destination.last_modified) {
+                (Some(source_modified), Some(destination_modified)) => {
+                    destination_modified >= source_modified
+                }
+                _ => false,
+            }
+        }
+        (Some(source_etag), Some(destination_etag)) => source_etag == destination_etag,
+        // Nothing to compare, copy it rather than risk missing a same size edit.
+        _ => false,
+    }
+}
+
+impl S3DirectoryAddr<'_> {
+    /// Mirrors this prefix into `destination`, only copying objects that are new or whose size or
+    /// ETag changed. With `delete` set, objects in the destination that no longer exist in the
+    /// source are removed, like `aws s3 sync --delete`, which is refused for an empty destination
+    /// prefix (see [`EmptyPrefixDeleteError`](crate::s3_generic::fetchers_and_getters::EmptyPrefixDeleteError)).
+    /// Objects that fail to copy or delete don't stop the rest, they are collected in
+    /// [`SyncReport::failed`].
+    pub async fn sync_into(
+        &self,
+        destination: &S3DirectoryAddr<'_>,
+        delete: bool,
+    ) -> anyhow::Result<SyncReport> {
+        self.sync_into_with_mode(destination, delete, CopyMode::default())
+            .await
+    }
+
+    /// Same as [`Self::sync_into`] with the [`CopyMode`] picked by the caller.
+    pub async fn sync_into_with_mode(
+        &self,
+        destination: &S3DirectoryAddr<'_>,
+        delete: bool,
+        mode: CopyMode,
+    ) -> anyhow::Result<SyncReport> {
+        info!(
+            src_bucket = %self.bucket,
+            src_prefix = %self.prefix,
+            dest_bucket = %destination.bucket,
+            dest_prefix = %destination.prefix,
+            %delete,
+            ?mode,
+            "Syncing S3 prefixes"
+        );
+        if delete {
+            destination.ensure_prefix_for_delete()?;
+        }
+        let (source_objects, destination_objects) =
+            tokio::try_join!(self.list_all_detailed(), destination.list_all_detailed())?;
+
+        let mut existing: HashMap<String, S3ObjectMeta> = destination_objects
+            .into_iter()
+            .filter_map(|object| {
+                let relative = object.key.strip_prefix(&*destination.prefix)?.to_string();
+                Some((relative, object))
+            })
+            .collect();
+
+        let mut report = SyncReport::default();
+        let mut to_copy = Vec::new();
+        for object in source_objects {
+            let relative = object
+                .key
+                .strip_prefix(&*self.prefix)
+                .unwrap_or(&object.key)
+                .to_string();
+            match existing.remove(&relative) {
+                Some(current) if is_unchanged(&object, &current) => report.skipped += 1,
+                _ => to_copy.push((object.key, relative)),
+            }
+        }
+
+        let copy_results: Vec<Result<(), (String, String)>> = stream::iter(to_copy)
+            .map(|(source_key, relative)| async move {
+                let destination_key = format!("{}{}", destination.prefix, relative);
+                let source = S3Addr::new(self.s3_client, self.bucket, &source_key);
+                let dest = S3Addr::new(destination.s3_client, destination.bucket, &destination_key);
+                debug!(src_key = %source_key, dest_key = %destination_key, "Syncing object");
+                source.copy_to_with_mode(&dest, mode).await.map_err(|err| {
+                    error!(%err, %source_key, %destination_key, "Failed to sync object");
+                    (source_key, err.to_string())
+                })
+            })
+            .buffer_unordered(DEFAULT_COPY_CONCURRENCY)
+            .collect()
+            .await;
+        for result in copy_results {
+            match result {
+                Ok(()) => report.copied += 1,
+                Err(failure) => report.failed.push(failure),
+            }
+        }
+
+        if delete && !existing.is_empty() {
+            let stale_keys: Vec<String> = existing.into_values().map(|object| object.key).collect();
+            // One batch at a time so a failed request only marks its own keys as failed.
+            for batch in stale_keys.chunks(MAX_DELETE_BATCH_SIZE) {
+                let failed = match destination.delete_keys(batch).await {
+                    Ok(failed) => failed,
+                    Err(err) => {
+                        let message = err.to_string();
+                        batch
+                            .iter()
+                            .map(|key| (key.clone(), message.clone()))
+                            .collect()
+                    }
+                };
+                report.deleted += batch.len() - failed.len();
+                report.failed.extend(failed);
+            }
+        }
+
+        info!(
+            copied = %report.copied,
+            deleted = %report.deleted,
+            skipped = %report.skipped,
+            failed = %report.failed.len(),
+            "Finished syncing S3 prefixes"
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn meta(etag: &str, modified_secs: i64) -> S3ObjectMeta {
+        S3ObjectMeta {
+            key: "key".to_string(),
+            size: 10,
+            last_modified: Utc.timestamp_opt(modified_secs, 0).single(),
+            etag: Some(etag.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_is_unchanged() {
+        assert!(is_unchanged(&meta("\"abc\"", 1), &meta("\"abc\"", 0)));
+        assert!(!is_unchanged(&meta("\"abc\"", 0), &meta("\"def\"", 1)));
+        // A multipart copy of a single PUT object, compared by timestamp instead.
+        assert!(is_unchanged(&meta("\"abc\"", 1), &meta("\"def-3\"", 2)));
+        assert!(!is_unchanged(&meta("\"abc\"", 2), &meta("\"def-3\"", 1)));
+        let no_etag = S3ObjectMeta {
+            etag: None,
+            ..meta("", 0)
+        };
+        assert!(!is_unchanged(&meta("\"abc\"", 0), &no_etag));
+    }
+}
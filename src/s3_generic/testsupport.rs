@@ -0,0 +1,59 @@
+//! Helpers for exercising the S3 code against a local MinIO or LocalStack instead of a real
+//! bucket. Point [`LOCAL_ENDPOINT_ENV`] at the local endpoint, e.g. `http://localhost:9000`.
+
+use aws_sdk_s3::Client;
+use tracing::warn;
+
+use crate::s3_generic::S3Credentials;
+use crate::s3_generic::fetchers_and_getters::{S3Bucket, ensure_bucket};
+
+/// Env var holding the local endpoint. Tests that need it should be `#[ignore]`d so they show up as
+/// skipped rather than passing when it is unset.
+pub const LOCAL_ENDPOINT_ENV: &str = "S3_TEST_ENDPOINT";
+/// Region the local endpoint is addressed with; MinIO and LocalStack accept anything.
+pub const LOCAL_REGION: &str = "us-east-1";
+/// Access and secret key of the default MinIO/LocalStack setups, overridable through
+/// `S3_TEST_ACCESS_KEY` and `S3_TEST_SECRET_KEY`.
+pub const DEFAULT_TEST_KEY: &str = "minioadmin";
+
+/// Path-style credentials with test keys for `endpoint`.
+pub fn local_credentials(endpoint: &str) -> S3Credentials {
+    let access_key =
+        std::env::var("S3_TEST_ACCESS_KEY").unwrap_or_else(|_| DEFAULT_TEST_KEY.to_string());
+    let secret_key =
+        std::env::var("S3_TEST_SECRET_KEY").unwrap_or_else(|_| DEFAULT_TEST_KEY.to_string());
+    S3Credentials::new(LOCAL_REGION, endpoint, access_key, secret_key).with_force_path_style(true)
+}
+
+/// A client for the endpoint in [`LOCAL_ENDPOINT_ENV`], or `None` when it is unset.
+pub async fn local_client() -> Option<Client> {
+    let endpoint = std::env::var(LOCAL_ENDPOINT_ENV).ok()?;
+    Some(local_credentials(&endpoint).make_s3_client().await)
+}
+
+/// A uniquely named bucket for a single test. Async cleanup can't run on drop, so call
+/// [`Self::cleanup`] at the end of the test.
+pub struct TempBucket {
+    pub bucket: S3Bucket,
+}
+
+impl TempBucket {
+    pub async fn create(s3_client: Client) -> anyhow::Result<Self> {
+        let name = format!("mycorrhiza-test-{:016x}", rand::random::<u64>());
+        ensure_bucket(&s3_client, &name).await?;
+        Ok(TempBucket {
+            bucket: S3Bucket::new(s3_client, name),
+        })
+    }
+
+    /// Deletes every object in the bucket and then the bucket itself.
+    pub async fn cleanup(self) -> anyhow::Result<()> {
+        self.bucket.delete_all_objects().await?;
+        let S3Bucket { s3_client, bucket } = self.bucket;
+        if let Err(err) = s3_client.delete_bucket().bucket(&bucket).send().await {
+            warn!(%err, %bucket, "Failed to delete temporary test bucket");
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use aws_sdk_s3::types::ServerSideEncryption;
+
+/// Server side encryption to request when storing an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum S3Encryption {
+    /// S3 managed keys (`AES256`).
+    Aes256,
+    /// KMS managed keys (`aws:kms`), using the bucket default key when `key_id` is `None`.
+    Kms { key_id: Option<String> },
+}
+
+/// Optional settings for uploads. The default leaves every header unset, so uploads behave exactly
+/// like the plain `upload_*` methods.
+#[derive(Debug, Clone, Default)]
+pub struct S3UploadOptions {
+    pub encryption: Option<S3Encryption>,
+    /// Overrides the `Content-Type` that would otherwise be inferred from the key's extension.
+    pub content_type: Option<String>,
+    /// Sent as the `Content-Encoding` header, e.g. `gzip` for pre-compressed bodies.
+    pub content_encoding: Option<String>,
+    /// Object tags as `(key, value)` pairs, sent url-encoded in the `Tagging` header.
+    pub tags: Vec<(String, String)>,
+    /// User metadata, stored by S3 as `x-amz-meta-*` headers.
+    pub metadata: HashMap<String, String>,
+    /// Sends `If-None-Match: *` so S3 refuses to replace an object that already exists.
+    pub only_if_absent: bool,
+}
+
+impl S3UploadOptions {
+    pub fn with_encryption(mut self, encryption: S3Encryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn with_content_encoding(mut self, content_encoding: impl Into<String>) -> Self {
+        self.content_encoding = Some(content_encoding.into());
+        self
+    }
+
+    pub fn with_tags(mut self, tags: &[(String, String)]) -> Self {
+        self.tags = tags.to_vec();
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_only_if_absent(mut self, only_if_absent: bool) -> Self {
+        self.only_if_absent = only_if_absent;
+        self
+    }
+
+    pub(crate) fn tagging(&self) -> Option<String> {
+        if self.tags.is_empty() {
+            return None;
+        }
+        let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+        for (key, value) in &self.tags {
+            serializer.append_pair(key, value);
+        }
+        Some(serializer.finish())
+    }
+
+    pub(crate) fn user_metadata(&self) -> Option<HashMap<String, String>> {
+        if self.metadata.is_empty() {
+            return None;
+        }
+        Some(self.metadata.clone())
+    }
+
+    pub(crate) fn server_side_encryption(&self) -> Option<ServerSideEncryption> {
+        self.encryption.as_ref().map(|encryption| match encryption {
+            S3Encryption::Aes256 => ServerSideEncryption::Aes256,
+            S3Encryption::Kms { .. } => ServerSideEncryption::AwsKms,
+        })
+    }
+
+    pub(crate) fn kms_key_id(&self) -> Option<String> {
+        match &self.encryption {
+            Some(S3Encryption::Kms { key_id }) => key_id.clone(),
+            _ => None,
+        }
+    }
+}
@@ -3,6 +3,13 @@ use routing::CHECK_TASK_URL_LEAF;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use progress::{ProgressReporter, ProgressSender, TaskProgress, new_progress_sender};
+pub mod pool;
+pub mod progress;
+pub mod retry;
 pub mod routing;
 pub mod workers;
 
@@ -23,8 +30,19 @@ pub fn map_err_as_json<T, E: std::fmt::Display + std::fmt::Debug>(
 #[async_trait]
 pub trait ExecuteUserTask: 'static + Send {
     async fn execute_task(self: Box<Self>) -> Result<Value, Value>;
+    /// Runs the task until it finishes or its cancellation token fires. Cancelling drops the task's
+    /// future at whatever await point it is suspended on, so cleanup belongs in `Drop` impls. The
+    /// task can report progress through [`ProgressReporter::current`].
     async fn execute_task_raw(self: Box<Self>, status: &mut TaskStatus) {
-        let return_result = self.execute_task().await;
+        let cancellation_token = status.cancellation_token.clone();
+        let reporter = ProgressReporter::new(status.progress.clone());
+        let return_result = tokio::select! {
+            result = reporter.scope(self.execute_task()) => result,
+            _ = cancellation_token.cancelled() => {
+                status.status = TaskState::Cancelled;
+                return;
+            }
+        };
         let status_val = match return_result {
             Ok(_) => TaskState::Successful,
             Err(_) => TaskState::Errored,
@@ -44,10 +62,11 @@ pub enum TaskState {
     Processing,
     Successful,
     Errored,
+    Cancelled,
 }
 impl TaskState {
     pub fn is_completed(&self) -> bool {
-        (*self == Self::Successful) || (*self == Self::Errored)
+        matches!(self, Self::Successful | Self::Errored | Self::Cancelled)
     }
 }
 
@@ -57,6 +76,8 @@ pub struct TaskStatus {
     pub status: TaskState,
     pub task_type_label: &'static str,
     pub return_value: Option<Result<Value, Value>>,
+    pub cancellation_token: CancellationToken,
+    progress: ProgressSender,
 }
 impl TaskStatus {
     fn new(task_id: u64, obj: &dyn ExecuteUserTask) -> Self {
@@ -65,8 +86,42 @@ impl TaskStatus {
             task_type_label: obj.get_task_label(),
             status: TaskState::Waiting,
             return_value: None,
+            cancellation_token: CancellationToken::new(),
+            progress: new_progress_sender(),
+        }
+    }
+    pub fn handle(&self) -> TaskHandle {
+        TaskHandle {
+            task_id: self.task_id,
+            cancellation_token: self.cancellation_token.clone(),
+            progress: self.progress.clone(),
         }
     }
+    pub fn latest_progress(&self) -> Option<TaskProgress> {
+        self.progress.borrow().clone()
+    }
+}
+
+/// Lets whoever queued a task cancel it later, see [`ExecuteUserTask::execute_task_raw`].
+#[derive(Clone, Debug)]
+pub struct TaskHandle {
+    pub task_id: u64,
+    cancellation_token: CancellationToken,
+    progress: ProgressSender,
+}
+
+impl TaskHandle {
+    /// Cancels the task, a task that hasn't started yet is skipped entirely.
+    pub fn cancel(&self) {
+        self.cancellation_token.cancel();
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+    /// Watches the latest progress the task reported, `None` until it first reports.
+    pub fn progress(&self) -> watch::Receiver<Option<TaskProgress>> {
+        self.progress.subscribe()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -77,6 +132,8 @@ pub struct TaskStatusDisplay {
     task_type_label: &'static str,
     check_url_leaf: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<TaskProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     sucess_info: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error_info: Option<Value>,
@@ -84,6 +141,7 @@ pub struct TaskStatusDisplay {
 
 impl From<TaskStatus> for TaskStatusDisplay {
     fn from(value: TaskStatus) -> Self {
+        let progress = value.latest_progress();
         let (success_val, err_val) = match value.return_value {
             None => (None, None),
             Some(Ok(success)) => (Some(success), None),
@@ -97,6 +155,7 @@ impl From<TaskStatus> for TaskStatusDisplay {
             check_url_leaf: url_leaf,
             completed: value.status.is_completed(),
             task_type_label: value.task_type_label,
+            progress,
             sucess_info: success_val,
             error_info: err_val,
         }
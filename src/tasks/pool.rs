@@ -0,0 +1,91 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+use tokio::task::{JoinError, JoinSet};
+
+/// Runs spawned futures on the tokio runtime with at most `max_concurrency` of them executing at
+/// once, the rest wait for a permit. Unlike the global task queue results stay local to the pool.
+pub struct TaskPool<T> {
+    semaphore: Arc<Semaphore>,
+    tasks: JoinSet<T>,
+}
+
+impl<T: Send + 'static> TaskPool<T> {
+    pub fn new(max_concurrency: usize) -> Self {
+        TaskPool {
+            semaphore: Arc::new(Semaphore::new(max_concurrency.max(1))),
+            tasks: JoinSet::new(),
+        }
+    }
+
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let semaphore = self.semaphore.clone();
+        self.tasks.spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .expect("The pool never closes its semaphore");
+            future.await
+        });
+    }
+
+    /// Number of spawned futures that haven't been joined yet.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Waits for the next future to finish, `None` once the pool is empty.
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        self.tasks.join_next().await
+    }
+
+    /// Waits for everything spawned so far, returning results in completion order. Panics if any of
+    /// the futures panicked, use [`Self::join_next`] to handle that instead.
+    pub async fn join_all(mut self) -> Vec<T> {
+        let mut results = Vec::with_capacity(self.len());
+        while let Some(result) = self.join_next().await {
+            match result {
+                Ok(value) => results.push(value),
+                Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+                Err(err) => panic!("Task pool future was cancelled: {err}"),
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_task_pool_caps_concurrency() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let mut pool = TaskPool::new(3);
+        for i in 0..20 {
+            let (running, peak) = (running.clone(), peak.clone());
+            pool.spawn(async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            });
+        }
+        let mut results = pool.join_all().await;
+        results.sort();
+        assert_eq!(results, (0..20).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+/// The latest progress a task reported.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TaskProgress {
+    /// From `0.0` to `1.0`.
+    pub fraction: f32,
+    pub message: String,
+}
+
+pub(crate) type ProgressSender = Arc<watch::Sender<Option<TaskProgress>>>;
+
+pub(crate) fn new_progress_sender() -> ProgressSender {
+    Arc::new(watch::channel(None).0)
+}
+
+tokio::task_local! {
+    static CURRENT_PROGRESS: ProgressReporter;
+}
+
+/// Publishes progress for the task currently being run by the workers, so the status endpoint and
+/// [`TaskHandle::progress`](super::TaskHandle::progress) can show it.
+#[derive(Clone, Debug, Default)]
+pub struct ProgressReporter {
+    sender: Option<ProgressSender>,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(sender: ProgressSender) -> Self {
+        ProgressReporter {
+            sender: Some(sender),
+        }
+    }
+
+    /// The reporter for the running task. Outside of a task, including inside futures the task
+    /// `tokio::spawn`s, reporting does nothing.
+    pub fn current() -> Self {
+        CURRENT_PROGRESS.try_with(Clone::clone).unwrap_or_default()
+    }
+
+    pub fn report(&self, fraction: f32, message: &str) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        sender.send_replace(Some(TaskProgress {
+            fraction: fraction.clamp(0.0, 1.0),
+            message: message.to_string(),
+        }));
+    }
+
+    /// Runs `future` with this as the [`Self::current`] reporter.
+    pub(crate) async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT_PROGRESS.scope(self, future).await
+    }
+}
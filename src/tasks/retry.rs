@@ -0,0 +1,46 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use tracing::warn;
+
+use super::ExecuteUserTask;
+use crate::misc::retry::retry_if;
+
+pub use crate::misc::retry::RetryPolicy as RetryConfig;
+
+/// Wraps a task so the runner re-runs a fresh clone of it whenever it returns `Err`, backing off
+/// between attempts. The error from the last attempt is what ends up in the task status.
+#[derive(Clone, Debug)]
+pub struct RetryingTask<T> {
+    pub task: T,
+    pub config: RetryConfig,
+}
+
+impl<T> RetryingTask<T> {
+    pub fn new(task: T, config: RetryConfig) -> Self {
+        RetryingTask { task, config }
+    }
+}
+
+#[async_trait]
+impl<T: ExecuteUserTask + Clone> ExecuteUserTask for RetryingTask<T> {
+    async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        let is_retryable = |err: &Value| {
+            warn!(error = %err, "Task failed, retrying");
+            true
+        };
+        let RetryingTask { task, config } = *self;
+        retry_if(config, is_retryable, move || {
+            Box::new(task.clone()).execute_task()
+        })
+        .await
+    }
+    fn get_task_label_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        T::get_task_label_static()
+    }
+    fn get_task_label(&self) -> &'static str {
+        self.task.get_task_label()
+    }
+}
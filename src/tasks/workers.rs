@@ -96,6 +96,20 @@ pub async fn add_task_to_queue(obj: impl ExecuteUserTask, priority: i32) -> Task
     task_status
 }
 
+/// Cancels the task with `task_id`, returning `false` if there is no such task.
+pub async fn cancel_task(task_id: u64) -> bool {
+    let mut task_status_writelock = (*TASK_STATUS_DATA).write().await;
+    let Some(status) = task_status_writelock.get_mut(&task_id) else {
+        return false;
+    };
+    status.handle().cancel();
+    // Waiting tasks are only skipped once popped, so report them as cancelled straight away.
+    if status.status == TaskState::Waiting {
+        status.status = TaskState::Cancelled;
+    }
+    true
+}
+
 pub async fn read_task_status(task_id: u64) -> Option<TaskStatus> {
     let read_guard = (*TASK_STATUS_DATA).read().await;
 
@@ -181,7 +195,12 @@ pub async fn task_poll_now() -> bool {
                         .cloned()
                         .unwrap_or_else(|| TaskStatus::new(task_id, &*obj));
                     drop(task_status_readlock);
-                    obj.execute_task_raw(&mut task_obj).await;
+                    if task_obj.cancellation_token.is_cancelled() {
+                        info!("Task was cancelled before it started, skipping it");
+                        task_obj.status = TaskState::Cancelled;
+                    } else {
+                        obj.execute_task_raw(&mut task_obj).await;
+                    }
 
                     let mut task_status_writelock = (*TASK_STATUS_DATA).write().await;
                     task_status_writelock.insert(task_id, task_obj);
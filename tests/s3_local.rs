@@ -0,0 +1,127 @@
+//! Round trips against a local S3, ignored by default since they need one running, e.g.
+//! `docker run -p 9000:9000 minio/minio server /data` and
+//! `S3_TEST_ENDPOINT=http://localhost:9000 cargo test --features testsupport -- --ignored`.
+#![cfg(feature = "testsupport")]
+
+use std::collections::HashMap;
+
+use mycorrhiza_common::s3_generic::compression::{
+    DEFAULT_ZSTD_LEVEL, ZSTD_CONTENT_ENCODING, zstd_compress,
+};
+use mycorrhiza_common::s3_generic::fetchers_and_getters::CopyMode;
+use mycorrhiza_common::s3_generic::testsupport::{TempBucket, local_client};
+use mycorrhiza_common::s3_generic::upload_options::S3UploadOptions;
+
+#[tokio::test]
+#[ignore = "requires S3_TEST_ENDPOINT"]
+async fn test_object_round_trips() {
+    let s3_client = local_client().await.expect("S3_TEST_ENDPOINT must be set");
+    let temp = TempBucket::create(s3_client).await.unwrap();
+    let bucket = &temp.bucket;
+
+    let object = bucket.obj("docs/2024 Q1/report.json");
+    object.upload_json(&vec![1, 2, 3]).await.unwrap();
+    assert!(object.exists().await.unwrap());
+    assert_eq!(
+        object.download_json::<Vec<u8>>().await.unwrap(),
+        vec![1, 2, 3]
+    );
+
+    let copy = bucket.obj("copies/2024 Q1/report.json");
+    object.copy_to(&copy).await.unwrap();
+    assert_eq!(
+        copy.download_json::<Vec<u8>>().await.unwrap(),
+        vec![1, 2, 3]
+    );
+
+    copy.delete_file().await.unwrap();
+    assert!(!copy.exists().await.unwrap());
+
+    let key = bucket
+        .upload_content_addressed(b"same".to_vec())
+        .await
+        .unwrap();
+    assert_eq!(
+        bucket
+            .upload_content_addressed(b"same".to_vec())
+            .await
+            .unwrap(),
+        key
+    );
+    assert_eq!(bucket.obj(&key).download_bytes().await.unwrap(), b"same");
+
+    temp.cleanup().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires S3_TEST_ENDPOINT"]
+async fn test_stream_copy_keeps_headers_metadata_and_tags() {
+    let s3_client = local_client().await.expect("S3_TEST_ENDPOINT must be set");
+    let temp = TempBucket::create(s3_client).await.unwrap();
+    let bucket = &temp.bucket;
+
+    let object = bucket.obj("compressed.json");
+    let metadata = HashMap::from([("source".to_string(), "scraper".to_string())]);
+    let tags = [("kind".to_string(), "test".to_string())];
+    let opts = S3UploadOptions::default()
+        .with_content_encoding(ZSTD_CONTENT_ENCODING)
+        .with_metadata(metadata.clone())
+        .with_tags(&tags);
+    let compressed = zstd_compress(b"[1,2,3]", DEFAULT_ZSTD_LEVEL).unwrap();
+    object
+        .upload_bytes_with_opts(compressed, &opts)
+        .await
+        .unwrap();
+
+    let copy = bucket.obj("copies/compressed.json");
+    object
+        .copy_to_with_mode(&copy, CopyMode::Stream)
+        .await
+        .unwrap();
+    assert_eq!(
+        copy.download_json_zstd::<Vec<u8>>().await.unwrap(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(copy.get_metadata().await.unwrap(), metadata);
+    assert_eq!(copy.get_tags().await.unwrap(), tags);
+
+    temp.cleanup().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires S3_TEST_ENDPOINT"]
+async fn test_directory_list_copy_and_delete() {
+    let s3_client = local_client().await.expect("S3_TEST_ENDPOINT must be set");
+    let temp = TempBucket::create(s3_client).await.unwrap();
+    let bucket = &temp.bucket;
+
+    for key in ["src/a.txt", "src/nested/b.txt", "src/nested/c.txt"] {
+        bucket.obj(key).upload_bytes(key.into()).await.unwrap();
+    }
+    let source = bucket.dir("src");
+    let mut listed = source.list_all().await.unwrap();
+    listed.sort();
+    assert_eq!(
+        listed,
+        ["src/a.txt", "src/nested/b.txt", "src/nested/c.txt"]
+    );
+
+    let destination = bucket.dir("dest");
+    let report = source.copy_into(&destination).await.unwrap();
+    assert_eq!(report.succeeded, 3);
+    assert!(report.failed.is_empty());
+    assert_eq!(
+        bucket
+            .obj("dest/nested/b.txt")
+            .download_bytes()
+            .await
+            .unwrap(),
+        b"src/nested/b.txt"
+    );
+
+    source.delete_all().await.unwrap();
+    assert!(source.list_all().await.unwrap().is_empty());
+    assert_eq!(destination.list_all().await.unwrap().len(), 3);
+
+    temp.cleanup().await.unwrap();
+}